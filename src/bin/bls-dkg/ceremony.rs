@@ -0,0 +1,313 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// https://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+use blsttc::{serde_impl::SerdeSecret, PublicKey, SecretKey};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use xor_name::{XorName, XOR_NAME_LEN};
+
+const CONFIG_FILE: &str = "ceremony.conf";
+
+/// The participants of a ceremony, as written to `ceremony.conf` by `init`:
+///
+/// ```text
+/// threshold <t>
+/// member <name in hex> <address> <public key in hex>
+/// ...
+/// ```
+///
+/// Members are numbered by the order of their names, as in the DKG context. The secret key of
+/// each member is kept apart, in `member-<index>.key`.
+pub struct Ceremony {
+    pub dir: PathBuf,
+    pub threshold: usize,
+    pub members: BTreeMap<XorName, SocketAddr>,
+    pub public_keys: BTreeMap<XorName, PublicKey>,
+}
+
+impl Ceremony {
+    pub fn load(dir: &Path) -> Result<Ceremony, String> {
+        let path = dir.join(CONFIG_FILE);
+        let text = fs::read_to_string(&path)
+            .map_err(|err| format!("Failed to read {}: {}", path.display(), err))?;
+        let mut threshold = None;
+        let mut members = BTreeMap::new();
+        let mut public_keys = BTreeMap::new();
+        for (number, line) in text.lines().enumerate() {
+            let invalid = || format!("{}:{}: invalid line {:?}", path.display(), number + 1, line);
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words.as_slice() {
+                [] => {}
+                [comment, ..] if comment.starts_with('#') => {}
+                ["threshold", t] => threshold = Some(t.parse().map_err(|_| invalid())?),
+                ["member", name, addr, public_key] => {
+                    let name = parse_name(name).ok_or_else(invalid)?;
+                    let addr = addr.parse().map_err(|_| invalid())?;
+                    let public_key = parse_hex(public_key)
+                        .and_then(|bytes| bincode::deserialize(&bytes).ok())
+                        .ok_or_else(invalid)?;
+                    let _ = members.insert(name, addr);
+                    let _ = public_keys.insert(name, public_key);
+                }
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(Ceremony {
+            dir: dir.to_path_buf(),
+            threshold: threshold.ok_or_else(|| format!("{} has no threshold", path.display()))?,
+            members,
+            public_keys,
+        })
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let mut text = format!(
+            "# bls-dkg ceremony of {} members\nthreshold {}\n",
+            self.members.len(),
+            self.threshold
+        );
+        for (name, addr) in self.members.iter() {
+            let public_key = self
+                .public_keys
+                .get(name)
+                .ok_or_else(|| format!("No public key of {}", name))?;
+            let public_key = bincode::serialize(public_key).map_err(|err| err.to_string())?;
+            text.push_str(&format!(
+                "member {} {} {}\n",
+                format_name(name),
+                addr,
+                format_hex(&public_key)
+            ));
+        }
+        write(&self.dir.join(CONFIG_FILE), text.as_bytes())
+    }
+
+    /// Writes the secret key of member `index`, readable by the owner only.
+    pub fn save_secret_key(&self, index: usize, secret_key: &SecretKey) -> Result<(), String> {
+        let bytes =
+            bincode::serialize(&SerdeSecret(secret_key.clone())).map_err(|err| err.to_string())?;
+        write_secret(&self.member_file(index, "key"), &bytes)
+    }
+
+    /// Reads the secret key of member `index`.
+    pub fn secret_key(&self, index: usize) -> Result<SecretKey, String> {
+        let path = self.member_file(index, "key");
+        fs::read(&path)
+            .map_err(|err| err.to_string())
+            .and_then(|bytes| {
+                bincode::deserialize::<SerdeSecret<SecretKey>>(&bytes)
+                    .map_err(|err| err.to_string())
+            })
+            .map(SerdeSecret::into_inner)
+            .map_err(|err| format!("Failed to read the key {}: {}", path.display(), err))
+    }
+
+    /// The name of member `index`.
+    pub fn member(&self, index: usize) -> Result<XorName, String> {
+        self.members
+            .keys()
+            .nth(index)
+            .copied()
+            .ok_or_else(|| format!("No member {} among {}", index, self.members.len()))
+    }
+
+    /// The file of member `index` with the given extension.
+    pub fn member_file(&self, index: usize, extension: &str) -> PathBuf {
+        self.dir.join(format!("member-{}.{}", index, extension))
+    }
+
+    /// The directory receiving the messages of `name` when running over files.
+    pub fn mailbox(&self, name: &XorName) -> PathBuf {
+        self.dir.join("mailbox").join(format_name(name))
+    }
+}
+
+pub fn format_name(name: &XorName) -> String {
+    format_hex(&name.0)
+}
+
+pub fn parse_name(hex: &str) -> Option<XorName> {
+    let bytes = parse_hex(hex)?;
+    if bytes.len() != XOR_NAME_LEN {
+        return None;
+    }
+    let mut name = [0; XOR_NAME_LEN];
+    name.copy_from_slice(&bytes);
+    Some(XorName(name))
+}
+
+fn format_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn parse_hex(hex: &str) -> Option<Vec<u8>> {
+    let pairs = hex.as_bytes().chunks_exact(2);
+    if !pairs.remainder().is_empty() || !hex.is_ascii() {
+        return None;
+    }
+    pairs
+        .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok())
+        .collect()
+}
+
+/// Writes `path` through a temporary file, so that readers never see it partially written.
+pub fn write(path: &Path, contents: &[u8]) -> Result<(), String> {
+    write_with(path, contents, &fs::OpenOptions::new())
+}
+
+/// Same as `write`, for files holding secrets: on Unix, the file is created readable and
+/// writable by the owner only.
+pub fn write_secret(path: &Path, contents: &[u8]) -> Result<(), String> {
+    let mut options = fs::OpenOptions::new();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        let _ = options.mode(0o600);
+    }
+    write_with(path, contents, &options)
+}
+
+fn write_with(path: &Path, contents: &[u8], options: &fs::OpenOptions) -> Result<(), String> {
+    let tmp = path.with_extension("tmp");
+    // A leftover temporary file would keep its permissions.
+    let _ = fs::remove_file(&tmp);
+    options
+        .clone()
+        .write(true)
+        .create_new(true)
+        .open(&tmp)
+        .and_then(|mut file| file.write_all(contents))
+        .and_then(|()| fs::rename(&tmp, path))
+        .map_err(|err| format!("Failed to write {}: {}", path.display(), err))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use std::env;
+
+    /// A ceremony of `participants` members in a fresh temporary directory, with their secret
+    /// keys.
+    pub fn temp_ceremony(
+        participants: usize,
+        threshold: usize,
+    ) -> (Ceremony, BTreeMap<XorName, SecretKey>) {
+        let dir = env::temp_dir().join(format!("bls-dkg-{}", format_name(&XorName::random())));
+        fs::create_dir_all(&dir).expect("a temporary directory");
+        let secret_keys: BTreeMap<XorName, SecretKey> = (0..participants)
+            .map(|_| (XorName::random(), SecretKey::random()))
+            .collect();
+        let ceremony = Ceremony {
+            dir,
+            threshold,
+            members: secret_keys
+                .keys()
+                .enumerate()
+                .map(|(index, name)| {
+                    (
+                        *name,
+                        SocketAddr::from(([127, 0, 0, 1], 7100 + index as u16)),
+                    )
+                })
+                .collect(),
+            public_keys: secret_keys
+                .iter()
+                .map(|(name, secret_key)| (*name, secret_key.public_key()))
+                .collect(),
+        };
+        (ceremony, secret_keys)
+    }
+
+    #[test]
+    fn ceremony_round_trip() {
+        let (ceremony, secret_keys) = temp_ceremony(4, 2);
+        ceremony.save().expect("saved");
+        for (index, secret_key) in secret_keys.values().enumerate() {
+            ceremony
+                .save_secret_key(index, secret_key)
+                .expect("key saved");
+        }
+
+        let loaded = Ceremony::load(&ceremony.dir).expect("loaded");
+        assert_eq!(loaded.dir, ceremony.dir);
+        assert_eq!(loaded.threshold, ceremony.threshold);
+        assert_eq!(loaded.members, ceremony.members);
+        assert_eq!(loaded.public_keys, ceremony.public_keys);
+        for (index, (name, secret_key)) in secret_keys.iter().enumerate() {
+            assert_eq!(loaded.member(index), Ok(*name));
+            assert!(loaded.secret_key(index).expect("key read") == *secret_key);
+        }
+        assert!(loaded.member(4).is_err());
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let metadata = fs::metadata(ceremony.member_file(0, "key")).expect("key file");
+            assert_eq!(metadata.permissions().mode() & 0o777, 0o600);
+        }
+        fs::remove_dir_all(&ceremony.dir).expect("removed");
+    }
+
+    #[test]
+    fn bad_lines_are_rejected() {
+        let (ceremony, _) = temp_ceremony(1, 1);
+        ceremony.save().expect("saved");
+        let path = ceremony.dir.join(CONFIG_FILE);
+        let text = fs::read_to_string(&path).expect("saved config");
+        let member = text
+            .lines()
+            .find(|line| line.starts_with("member "))
+            .expect("a member line");
+        let words: Vec<&str> = member.split_whitespace().collect();
+        let name = words[1];
+        let public_key = words[3];
+
+        let bad_lines = [
+            "threshold".to_string(),
+            "threshold two".to_string(),
+            format!("member {} 127.0.0.1:7100", name),
+            format!("member {} localhost {}", name, public_key),
+            format!("member {} 127.0.0.1:7100 {}", &name[1..], public_key),
+            format!("member {} 127.0.0.1:7100 {}", name, &public_key[2..]),
+            format!("member {} 127.0.0.1:7100 {} extra", name, public_key),
+            "participants 4".to_string(),
+        ];
+        for line in bad_lines.iter() {
+            fs::write(&path, format!("threshold 1\n{}\n", line)).expect("written");
+            let err = Ceremony::load(&ceremony.dir).err().expect("invalid line");
+            assert!(err.contains(":2: invalid line"), "{}", err);
+        }
+
+        // Comments and blank lines are skipped, but the threshold is required.
+        fs::write(&path, format!("# no threshold\n\n{}\n", member)).expect("written");
+        let err = Ceremony::load(&ceremony.dir).err().expect("no threshold");
+        assert!(err.ends_with("has no threshold"), "{}", err);
+        fs::remove_dir_all(&ceremony.dir).expect("removed");
+    }
+
+    #[test]
+    fn names_are_parsed_from_hex() {
+        let name = XorName::random();
+        let hex = format_name(&name);
+        assert_eq!(hex.len(), 2 * XOR_NAME_LEN);
+        assert_eq!(parse_name(&hex), Some(name));
+        assert_eq!(parse_name(&hex.to_uppercase()), Some(name));
+
+        // Too short, too long, of odd length, not hex, not ASCII.
+        assert_eq!(parse_name(&hex[2..]), None);
+        assert_eq!(parse_name(&format!("{}00", hex)), None);
+        assert_eq!(parse_name(&hex[1..]), None);
+        assert_eq!(parse_name(&format!("zz{}", &hex[2..])), None);
+        assert_eq!(parse_name(&format!("é{}", &hex[2..])), None);
+        assert_eq!(parse_name(""), None);
+    }
+}
@@ -0,0 +1,210 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// https://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+use crate::ceremony::{format_name, parse_name, Ceremony};
+use async_trait::async_trait;
+use bls_dkg::message::Message;
+use bls_dkg::session::{DkgTransport, TransportError};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use xor_name::XorName;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// A `DkgTransport` over a shared directory: each member has a mailbox directory, in which the
+/// others drop one file per message, named `<time>-<sender>-<run>-<sequence>.msg`. The time the
+/// message was dropped at comes first, so that messages are taken in the order they arrived
+/// whoever sent them.
+pub struct MailboxTransport {
+    our_id: XorName,
+    inbox: PathBuf,
+    peers: BTreeMap<XorName, PathBuf>,
+    // Tells apart the messages of successive runs of the same member.
+    run: u128,
+    next_seq: u64,
+}
+
+impl MailboxTransport {
+    pub fn new(ceremony: &Ceremony, our_id: XorName) -> Result<MailboxTransport, String> {
+        let inbox = ceremony.mailbox(&our_id);
+        fs::create_dir_all(&inbox)
+            .map_err(|err| format!("Failed to create {}: {}", inbox.display(), err))?;
+        let peers = ceremony
+            .members
+            .keys()
+            .filter(|name| **name != our_id)
+            .map(|name| (*name, ceremony.mailbox(name)))
+            .collect();
+        Ok(MailboxTransport {
+            our_id,
+            inbox,
+            peers,
+            run: now(),
+            next_seq: 0,
+        })
+    }
+
+    fn drop_into(&mut self, mailbox: &PathBuf, bytes: &[u8]) -> Result<(), TransportError> {
+        let file = format!(
+            "{:039}-{}-{:039}-{:020}",
+            now(),
+            format_name(&self.our_id),
+            self.run,
+            self.next_seq
+        );
+        self.next_seq += 1;
+        fs::create_dir_all(mailbox)
+            .and_then(|()| fs::write(mailbox.join(format!(".{}", file)), bytes))
+            .and_then(|()| {
+                fs::rename(
+                    mailbox.join(format!(".{}", file)),
+                    mailbox.join(format!("{}.msg", file)),
+                )
+            })
+            .map_err(|_| TransportError::Closed)
+    }
+
+    // Takes the message that arrived first out of our mailbox, skipping malformed ones.
+    fn take(&self) -> Option<(XorName, Message)> {
+        let mut files: Vec<PathBuf> = fs::read_dir(&self.inbox)
+            .ok()?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().and_then(|ext| ext.to_str()) == Some("msg"))
+            .collect();
+        files.sort();
+        for path in files {
+            let bytes = fs::read(&path);
+            let _ = fs::remove_file(&path);
+            let sender = path
+                .file_name()
+                .and_then(|file| file.to_str())
+                .and_then(|file| file.split('-').nth(1))
+                .and_then(parse_name);
+            match (sender, bytes.map(|bytes| bincode::deserialize(&bytes))) {
+                (Some(sender), Ok(Ok(msg))) => return Some((sender, msg)),
+                _ => eprintln!("Skipped malformed message {}", path.display()),
+            }
+        }
+        None
+    }
+}
+
+// Returns the nanoseconds elapsed since the Unix epoch.
+fn now() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_nanos())
+        .unwrap_or_default()
+}
+
+#[async_trait]
+impl DkgTransport for MailboxTransport {
+    async fn send_to(&mut self, peer: XorName, msg: Message) -> Result<(), TransportError> {
+        let mailbox = self
+            .peers
+            .get(&peer)
+            .cloned()
+            .ok_or(TransportError::UnknownPeer(peer))?;
+        let bytes = bincode::serialize(&msg)
+            .map_err(|err| TransportError::Serialisation(err.to_string()))?;
+        self.drop_into(&mailbox, &bytes)
+    }
+
+    async fn broadcast(&mut self, msg: Message) -> Result<(), TransportError> {
+        let bytes = bincode::serialize(&msg)
+            .map_err(|err| TransportError::Serialisation(err.to_string()))?;
+        let mailboxes: Vec<PathBuf> = self.peers.values().cloned().collect();
+        for mailbox in mailboxes.iter() {
+            self.drop_into(mailbox, &bytes)?;
+        }
+        Ok(())
+    }
+
+    async fn receive(&mut self) -> Option<(XorName, Message)> {
+        // Messages are only taken out of the mailbox right before being returned, which keeps
+        // this cancel safe.
+        loop {
+            if let Some(received) = self.take() {
+                return Some(received);
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ceremony::tests::temp_ceremony;
+    use bls_dkg::identity::Identity;
+    use bls_dkg::params::DkgParams;
+    use bls_dkg::session::{run_dkg, SessionParams, TokioClock};
+    use bls_dkg::sharexorname::ShareXorName;
+    use std::collections::BTreeSet;
+
+    #[tokio::test]
+    async fn members_complete_a_session_through_their_mailboxes() {
+        let (ceremony, secret_keys) = temp_ceremony(4, 2);
+        let context = ShareXorName::from_xornames(ceremony.members.keys().copied().collect());
+        let handles: Vec<_> = secret_keys
+            .iter()
+            .map(|(our_id, secret_key)| {
+                let identity = Identity::new(secret_key.clone(), ceremony.public_keys.clone());
+                let dkg = DkgParams::builder(*our_id, context.clone(), ceremony.threshold)
+                    .identity(identity)
+                    .build()
+                    .expect("valid params");
+                let mut params = SessionParams::new(dkg);
+                params.phase_timeout = Duration::from_secs(5);
+                params.resend_interval = Duration::from_secs(1);
+                let transport = MailboxTransport::new(&ceremony, *our_id).expect("a mailbox");
+                tokio::spawn(run_dkg(transport, params, TokioClock))
+            })
+            .collect();
+
+        let mut public_keys = BTreeSet::new();
+        for handle in handles {
+            let outcome = handle.await.expect("no panic").expect("completed");
+            let _ = public_keys.insert(outcome.public_key_set.public_key());
+        }
+        assert_eq!(public_keys.len(), 1);
+        fs::remove_dir_all(&ceremony.dir).expect("removed");
+    }
+
+    #[tokio::test]
+    async fn messages_are_taken_in_the_order_they_arrived() {
+        let (ceremony, _) = temp_ceremony(3, 1);
+        let names: Vec<XorName> = ceremony.members.keys().copied().collect();
+        let context = ShareXorName::from_xornames(names.clone());
+        let mut transports: Vec<MailboxTransport> = names
+            .iter()
+            .map(|name| MailboxTransport::new(&ceremony, *name).expect("a mailbox"))
+            .collect();
+
+        // The later member sends first, so that ordering by sender would starve it.
+        for sender in [2, 1, 2, 1].iter() {
+            let msg = Message::SyncRequest {
+                key_gen_id: *sender as u64,
+                context: context.clone(),
+            };
+            transports[*sender]
+                .send_to(names[0], msg)
+                .await
+                .expect("sent");
+        }
+        let senders: Vec<XorName> = (0..4)
+            .map(|_| transports[0].take().expect("a message").0)
+            .collect();
+        assert_eq!(senders, vec![names[2], names[1], names[2], names[1]]);
+        assert!(transports[0].take().is_none());
+        fs::remove_dir_all(&ceremony.dir).expect("removed");
+    }
+}
@@ -0,0 +1,286 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// https://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+//! Runs a DKG ceremony among local processes, e.g. for key ceremonies and debugging:
+//!
+//! ```text
+//! bls-dkg init <dir> --participants <n> --threshold <t> [--base-port <port>]
+//! bls-dkg run <dir> --member <index> [--transport tcp|files] [--phase-timeout <secs>]
+//!     [--attempts <n>]
+//! bls-dkg status <dir>
+//! bls-dkg export <dir> --member <index> --out <file>
+//! ```
+//!
+//! `init` writes `ceremony.conf`, which lists the members with their names, addresses and public
+//! keys, a `member-<index>.id` file with the name of each member and a `member-<index>.key` file
+//! with its secret key, signing its votes. Every member then runs `run` in its
+//! own process, talking to the others over localhost TCP or through mailbox directories in
+//! `<dir>`, and records its result in `member-<index>.status` and `member-<index>.outcome`. A
+//! failed session is run again without the members blamed for it, up to `--attempts` times.
+
+mod ceremony;
+mod mailbox;
+
+use bls_dkg::identity::Identity;
+use bls_dkg::outcome::Outcome;
+use bls_dkg::params::DkgParams;
+use bls_dkg::session::{DkgOrchestrator, DkgTransport, SessionParams, TcpTransport, TokioClock};
+use bls_dkg::sharexorname::ShareXorName;
+use blsttc::SecretKey;
+use ceremony::{format_name, write, write_secret, Ceremony};
+use mailbox::MailboxTransport;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use std::{env, fs, process};
+use xor_name::XorName;
+
+const USAGE: &str = "Usage:
+    bls-dkg init <dir> --participants <n> --threshold <t> [--base-port <port>]
+    bls-dkg run <dir> --member <index> [--transport tcp|files] [--phase-timeout <secs>]
+        [--attempts <n>]
+    bls-dkg status <dir>
+    bls-dkg export <dir> --member <index> --out <file>";
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let result = match args.split_first() {
+        Some((command, rest)) => Args::parse(rest).and_then(|args| match command.as_str() {
+            "init" => init(&args),
+            "run" => run(&args),
+            "status" => status(&args),
+            "export" => export(&args),
+            _ => Err(format!("Unknown command {}", command)),
+        }),
+        None => Err("Missing command".to_string()),
+    };
+    if let Err(err) = result {
+        eprintln!("{}\n\n{}", err, USAGE);
+        process::exit(1);
+    }
+}
+
+// The ceremony directory followed by `--option value` pairs.
+struct Args {
+    dir: PathBuf,
+    options: BTreeMap<String, String>,
+}
+
+impl Args {
+    fn parse(args: &[String]) -> Result<Args, String> {
+        let (dir, mut rest) = match args.split_first() {
+            Some((dir, rest)) => (PathBuf::from(dir), rest),
+            None => return Err("Missing ceremony directory".to_string()),
+        };
+        let mut options = BTreeMap::new();
+        while let [option, value, tail @ ..] = rest {
+            let name = option
+                .strip_prefix("--")
+                .ok_or_else(|| format!("Unexpected argument {}", option))?;
+            let _ = options.insert(name.to_string(), value.clone());
+            rest = tail;
+        }
+        if let [option] = rest {
+            return Err(format!("Missing value of {}", option));
+        }
+        Ok(Args { dir, options })
+    }
+
+    fn get<T: std::str::FromStr>(&self, name: &str, default: Option<T>) -> Result<T, String> {
+        match self.options.get(name) {
+            Some(value) => value
+                .parse()
+                .map_err(|_| format!("Invalid value of --{}: {}", name, value)),
+            None => default.ok_or_else(|| format!("Missing --{}", name)),
+        }
+    }
+}
+
+fn init(args: &Args) -> Result<(), String> {
+    let participants: usize = args.get("participants", None)?;
+    let threshold: usize = args.get("threshold", None)?;
+    let base_port: u16 = args.get("base-port", Some(7100))?;
+
+    let secret_keys: BTreeMap<XorName, SecretKey> = (0..participants)
+        .map(|_| (XorName::random(), SecretKey::random()))
+        .collect();
+    let public_keys: BTreeMap<_, _> = secret_keys
+        .iter()
+        .map(|(name, secret_key)| (*name, secret_key.public_key()))
+        .collect();
+    let (first, first_key) = secret_keys
+        .iter()
+        .next()
+        .ok_or("A ceremony needs participants")?;
+    // Validates the threshold before writing anything.
+    let context = ShareXorName::from_xornames(secret_keys.keys().copied().collect());
+    let _ = DkgParams::builder(*first, context, threshold)
+        .identity(Identity::new(first_key.clone(), public_keys.clone()))
+        .build()
+        .map_err(|err| err.to_string())?;
+    let members = secret_keys
+        .keys()
+        .enumerate()
+        .map(|(index, name)| {
+            let port = u16::try_from(index)
+                .ok()
+                .and_then(|index| base_port.checked_add(index))
+                .ok_or_else(|| {
+                    format!(
+                        "{} members don't fit in the ports from {}",
+                        participants, base_port
+                    )
+                })?;
+            Ok((*name, SocketAddr::from(([127, 0, 0, 1], port))))
+        })
+        .collect::<Result<BTreeMap<_, _>, String>>()?;
+
+    fs::create_dir_all(&args.dir)
+        .map_err(|err| format!("Failed to create {}: {}", args.dir.display(), err))?;
+    let ceremony = Ceremony {
+        dir: args.dir.clone(),
+        threshold,
+        members,
+        public_keys,
+    };
+    ceremony.save()?;
+    for (index, (name, secret_key)) in secret_keys.iter().enumerate() {
+        write(
+            &ceremony.member_file(index, "id"),
+            format!("{}\n", format_name(name)).as_bytes(),
+        )?;
+        ceremony.save_secret_key(index, secret_key)?;
+    }
+    println!(
+        "Initialised a ceremony of {} members with threshold {} in {}",
+        participants,
+        threshold,
+        args.dir.display()
+    );
+    Ok(())
+}
+
+fn run(args: &Args) -> Result<(), String> {
+    let ceremony = Ceremony::load(&args.dir)?;
+    let index: usize = args.get("member", None)?;
+    let transport: String = args.get("transport", Some("tcp".to_string()))?;
+    let phase_timeout: u64 = args.get("phase-timeout", Some(10))?;
+    let attempts: usize = args.get("attempts", Some(3))?;
+
+    let our_id = ceremony.member(index)?;
+    let identity = Identity::new(ceremony.secret_key(index)?, ceremony.public_keys.clone());
+    let context = ShareXorName::from_xornames(ceremony.members.keys().copied().collect());
+    let dkg = DkgParams::builder(our_id, context, ceremony.threshold)
+        .identity(identity)
+        .build()
+        .map_err(|err| err.to_string())?;
+    let mut params = SessionParams::new(dkg);
+    params.phase_timeout = Duration::from_secs(phase_timeout);
+    // Ask for missed messages a few times before a phase times out.
+    params.resend_interval = params.phase_timeout / 3;
+    let orchestrator = DkgOrchestrator::new(params).max_attempts(attempts);
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|err| format!("Failed to start the runtime: {}", err))?;
+    let status_file = ceremony.member_file(index, "status");
+    write(&status_file, b"running\n")?;
+    println!("Member {} ({}) joined the ceremony", index, our_id);
+
+    let result = runtime.block_on(async {
+        match transport.as_str() {
+            "tcp" => {
+                let addr = ceremony.members[&our_id];
+                let mut transport = TcpTransport::bind(our_id, addr, ceremony.members.clone())
+                    .await
+                    .map_err(|err| format!("Failed to listen on {}: {}", addr, err))?;
+                let result = session(&mut transport, orchestrator).await;
+                // The others may still need our last messages.
+                transport.close().await;
+                result
+            }
+            "files" => session(MailboxTransport::new(&ceremony, our_id)?, orchestrator).await,
+            _ => Err(format!("Unknown transport {}", transport)),
+        }
+    });
+
+    match result {
+        Ok(outcome) => {
+            let bytes = outcome.to_bytes().map_err(|err| err.to_string())?;
+            // The outcome holds our secret key share.
+            write_secret(&ceremony.member_file(index, "outcome"), &bytes)?;
+            let public_key = outcome.public_key_set.public_key();
+            write(
+                &status_file,
+                format!("finalized {:?}\n", public_key).as_bytes(),
+            )?;
+            println!("Generated public key {:?}", public_key);
+            Ok(())
+        }
+        Err(err) => {
+            write(&status_file, format!("failed: {}\n", err).as_bytes())?;
+            Err(err)
+        }
+    }
+}
+
+async fn session<T: DkgTransport>(
+    transport: T,
+    mut orchestrator: DkgOrchestrator,
+) -> Result<Outcome, String> {
+    let result = orchestrator.run(transport, TokioClock).await;
+    for (index, attempt) in orchestrator.history().iter().enumerate() {
+        if let Some(failure) = &attempt.failure {
+            println!("Attempt {} failed: {}", index + 1, failure);
+        }
+    }
+    result.map_err(|failure| failure.to_string())
+}
+
+fn status(args: &Args) -> Result<(), String> {
+    let ceremony = Ceremony::load(&args.dir)?;
+    println!(
+        "{} members, threshold {}",
+        ceremony.members.len(),
+        ceremony.threshold
+    );
+    for (index, (name, addr)) in ceremony.members.iter().enumerate() {
+        let status = fs::read_to_string(ceremony.member_file(index, "status"))
+            .unwrap_or_else(|_| "not started".to_string());
+        println!("#{} {} {}: {}", index, name, addr, status.trim_end());
+    }
+    Ok(())
+}
+
+fn export(args: &Args) -> Result<(), String> {
+    let ceremony = Ceremony::load(&args.dir)?;
+    let index: usize = args.get("member", None)?;
+    let out: PathBuf = args.get("out", None)?;
+
+    let outcome = read_outcome(&ceremony.member_file(index, "outcome"))?;
+    let bytes = outcome.to_bytes().map_err(|err| err.to_string())?;
+    write_secret(&out, &bytes)?;
+    println!(
+        "Exported share {} of public key {:?} to {}",
+        outcome.index,
+        outcome.public_key_set.public_key(),
+        out.display()
+    );
+    Ok(())
+}
+
+fn read_outcome(path: &Path) -> Result<Outcome, String> {
+    fs::read(path)
+        .map_err(|err| err.to_string())
+        .and_then(|bytes| Outcome::from_bytes(&bytes).map_err(|err| err.to_string()))
+        .map_err(|err| format!("Failed to read the outcome {}: {}", path.display(), err))
+}
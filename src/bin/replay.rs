@@ -0,0 +1,83 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// https://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+//! Replays a transcript saved with `Transcript::to_bytes`, printing the phase and the result of
+//! each step, followed by the generated keys or the reason they could not be generated.
+//!
+//! A transcript is as sensitive as the secret-key share of its member: see `Transcript`.
+
+use bls_dkg::transcript::{Transcript, TranscriptEntry};
+use std::{env, fs, process};
+
+fn main() {
+    let path = match env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("Usage: bls_dkg-replay <transcript>");
+            process::exit(2);
+        }
+    };
+    let transcript = match fs::read(&path)
+        .map_err(|err| err.to_string())
+        .and_then(|bytes| Transcript::from_bytes(&bytes).map_err(|err| err.to_string()))
+    {
+        Ok(transcript) => transcript,
+        Err(err) => {
+            eprintln!("Failed to read transcript {}: {}", path, err);
+            process::exit(1);
+        }
+    };
+
+    eprintln!(
+        "Warning: {} holds the secret keys of its member. Anyone reading it can rebuild the \
+         secret-key share: keep it as protected as the share itself.",
+        path
+    );
+    let params = transcript.params();
+    println!(
+        "Member {} of {}, threshold {}, mode {:?}",
+        params.our_id(),
+        params.names().len(),
+        params.threshold(),
+        params.mode()
+    );
+    let replay = match transcript.replay() {
+        Ok(replay) => replay,
+        Err(err) => {
+            eprintln!("Failed to create the KeyGen: {}", err);
+            process::exit(1);
+        }
+    };
+
+    for (index, step) in replay.steps.iter().enumerate() {
+        let input = match &step.entry {
+            TranscriptEntry::Message { msg, .. } => format!("{:?}", msg),
+            TranscriptEntry::TimedTransition { .. } => "TimedTransition".to_string(),
+        };
+        let result = match &step.result {
+            Ok(msgs) => format!("sent {:?}", msgs),
+            Err(err) => format!("error: {}", err),
+        };
+        println!("#{} {} -> {:?}, {}", index, input, step.phase, result);
+        for event in step.events.iter() {
+            println!("    {:?}", event);
+        }
+        if step.rng_exhausted {
+            println!("    diverged: drew more random bytes than were recorded");
+        }
+    }
+
+    match replay.outcome() {
+        Ok((_, outcome)) => println!(
+            "Generated public key {:?}",
+            outcome.public_key_set.public_key()
+        ),
+        Err(err) => println!("No keys generated: {}", err),
+    }
+}
@@ -0,0 +1,110 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// https://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+use crate::key_gen::{message::Message, tamper};
+use rand::rngs::StdRng;
+
+/// **NOT FOR PRODUCTION USE**: A malicious behaviour of a `SimNetwork` member. The member runs an
+/// honest `KeyGen`, but every message it sends passes through its adversary first.
+pub trait Adversary {
+    /// Returns the messages actually sent to member `to` in place of the honest `msg`.
+    fn tamper(&mut self, rng: &mut StdRng, to: usize, msg: &Message) -> Vec<Message>;
+}
+
+/// Sends a `Part` with a row that does not match its commitment to `victim`.
+pub struct InconsistentPart {
+    pub victim: u64,
+}
+
+impl Adversary for InconsistentPart {
+    fn tamper(&mut self, rng: &mut StdRng, _to: usize, msg: &Message) -> Vec<Message> {
+        let mut msg = msg.clone();
+        if let Message::Proposal { part, .. } = &mut msg {
+            if part.receiver == self.victim {
+                let _ = tamper::corrupt_row(rng, part);
+            }
+        }
+        vec![msg]
+    }
+}
+
+/// Follows each of its `Part`s with a valid `Part` of another polynomial.
+pub struct DoubleProposal;
+
+impl Adversary for DoubleProposal {
+    fn tamper(&mut self, rng: &mut StdRng, _to: usize, msg: &Message) -> Vec<Message> {
+        let mut msgs = vec![msg.clone()];
+        if let Message::Proposal {
+            key_gen_id,
+            context,
+            part,
+        } = msg
+        {
+            if let Ok(forged) = tamper::forge_part(rng, context, *key_gen_id, part) {
+                msgs.push(Message::Proposal {
+                    key_gen_id: *key_gen_id,
+                    context: context.clone(),
+                    part: forged,
+                });
+            }
+        }
+        msgs
+    }
+}
+
+/// Acknowledges random values instead of those of the received rows.
+pub struct WrongAcks;
+
+impl Adversary for WrongAcks {
+    fn tamper(&mut self, rng: &mut StdRng, _to: usize, msg: &Message) -> Vec<Message> {
+        let mut msg = msg.clone();
+        if let Message::Acknowledgment { ack, .. } = &mut msg {
+            let _ = tamper::corrupt_ack(rng, ack);
+        }
+        vec![msg]
+    }
+}
+
+/// Tells members with an odd index that the threshold is one lower than it is.
+pub struct EquivocatingInitialization;
+
+impl Adversary for EquivocatingInitialization {
+    fn tamper(&mut self, _rng: &mut StdRng, to: usize, msg: &Message) -> Vec<Message> {
+        let mut msg = msg.clone();
+        if let Message::Initialization { m, .. } = &mut msg {
+            if to % 2 == 1 {
+                *m -= 1;
+            }
+        }
+        vec![msg]
+    }
+}
+
+/// Sends one of `messages`, e.g. recorded during an earlier session, along with every message.
+pub struct ReplayOldContext {
+    pub messages: Vec<Message>,
+    next: usize,
+}
+
+impl ReplayOldContext {
+    pub fn new(messages: Vec<Message>) -> Self {
+        ReplayOldContext { messages, next: 0 }
+    }
+}
+
+impl Adversary for ReplayOldContext {
+    fn tamper(&mut self, _rng: &mut StdRng, _to: usize, msg: &Message) -> Vec<Message> {
+        let mut msgs = vec![msg.clone()];
+        if !self.messages.is_empty() {
+            msgs.push(self.messages[self.next % self.messages.len()].clone());
+            self.next += 1;
+        }
+        msgs
+    }
+}
@@ -7,6 +7,16 @@
 // specific language governing permissions and limitations relating to use of the SAFE Network
 // Software.
 
+//! **NOT FOR PRODUCTION USE**: Mock IDs and a simulated network to test the DKG with, available
+//! with the `test-utils` feature.
+
+mod adversary;
+mod network;
 mod peer;
 
+pub use self::adversary::{
+    Adversary, DoubleProposal, EquivocatingInitialization, InconsistentPart, ReplayOldContext,
+    WrongAcks,
+};
+pub use self::network::{SimConfig, SimError, SimNetwork};
 pub use self::peer::{create_ids, PeerId};
@@ -0,0 +1,360 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// https://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+use super::{Adversary, PeerId};
+use crate::key_gen::{
+    message::Message, mode::Mode, params::DkgParams, sharexorname::ShareXorName, Error, KeyGen,
+    Phase,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::collections::{BTreeMap, BTreeSet};
+use xor_name::XorName;
+
+/// How a `SimNetwork` delivers messages. Times are in virtual ticks.
+#[derive(Clone, Debug)]
+pub struct SimConfig {
+    /// Seeds every random choice of the simulation, including those of the `KeyGen`s, so that a
+    /// run can be reproduced.
+    pub seed: u64,
+    /// The delay of each delivery is drawn from `min_delay..=max_delay`. Any spread between the
+    /// two reorders messages.
+    pub min_delay: u64,
+    pub max_delay: u64,
+    /// The probability, between 0 and 1, that a delivery is lost.
+    pub drop_probability: f64,
+    /// The probability, between 0 and 1, that a delivery happens twice.
+    pub duplicate_probability: f64,
+    /// How long a member stays in the `Contribution` or `Complaining` phase before it calls
+    /// `KeyGen::timed_phase_transition`.
+    pub phase_timeout: u64,
+    /// The keygen id of the session, to run the same members in another session.
+    pub keygenid: [u8; 32],
+}
+
+impl Default for SimConfig {
+    fn default() -> Self {
+        SimConfig {
+            seed: 0,
+            min_delay: 1,
+            max_delay: 1,
+            drop_probability: 0.0,
+            duplicate_probability: 0.0,
+            phase_timeout: 1_000,
+            keygenid: [0; 32],
+        }
+    }
+}
+
+/// An error returned by a member of a `SimNetwork`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SimError {
+    pub time: u64,
+    pub node: usize,
+    pub error: Error,
+}
+
+struct SimNode {
+    peer_id: PeerId,
+    key_gen: KeyGen,
+    crashed: bool,
+    // The phase of `key_gen` and when it was entered, to fire the phase timer.
+    phase: Phase,
+    phase_since: u64,
+}
+
+struct Delivery {
+    from: usize,
+    to: usize,
+    msg: Message,
+}
+
+/// A deterministic simulation of a DKG session among `KeyGen`s exchanging messages over an
+/// unreliable network, in virtual time.
+///
+/// Every message is broadcast to all members, including its sender, as the `KeyGen` expects.
+pub struct SimNetwork {
+    config: SimConfig,
+    rng: StdRng,
+    nodes: Vec<SimNode>,
+    /// Deliveries in flight, by delivery time then sending order.
+    queue: BTreeMap<(u64, u64), Delivery>,
+    next_seq: u64,
+    now: u64,
+    partitions: Vec<BTreeSet<usize>>,
+    adversaries: BTreeMap<usize, Box<dyn Adversary>>,
+    /// Every message sent by an honest `KeyGen`, with its sender.
+    sent: Vec<(usize, Message)>,
+    errors: Vec<SimError>,
+}
+
+impl SimNetwork {
+    /// Creates `node_count` members running a session of the given threshold and mode, and
+    /// sends out their `Initialization` messages. Members are indexed by the order of their
+    /// names, i.e. by their index in the context.
+    pub fn new(
+        config: SimConfig,
+        node_count: usize,
+        threshold: usize,
+        mode: Mode,
+    ) -> Result<SimNetwork, Error> {
+        let mut rng = StdRng::seed_from_u64(config.seed);
+        let mut peer_ids: Vec<PeerId> = (0..node_count)
+            .map(|_| PeerId::with_rng(&mut rng))
+            .collect();
+        peer_ids.sort();
+        let names: BTreeSet<XorName> = peer_ids.iter().map(|peer_id| peer_id.name()).collect();
+        let mut context = ShareXorName::from_xornames(names.into_iter().collect());
+        context.set_keygenid(config.keygenid);
+
+        let mut network = SimNetwork {
+            config,
+            rng,
+            nodes: Vec::new(),
+            queue: BTreeMap::new(),
+            next_seq: 0,
+            now: 0,
+            partitions: Vec::new(),
+            adversaries: BTreeMap::new(),
+            sent: Vec::new(),
+            errors: Vec::new(),
+        };
+        let mut initializations = Vec::new();
+        for peer_id in peer_ids.iter().cloned() {
+            let params = DkgParams::builder(peer_id.name(), context.clone(), threshold)
+                .mode(mode.clone())
+                .identity(peer_id.identity(&peer_ids))
+                .build()?;
+            let (key_gen, msg) = KeyGen::with_params(&mut network.rng, params)?;
+            network.nodes.push(SimNode {
+                peer_id,
+                phase: key_gen.phase(),
+                key_gen,
+                crashed: false,
+                phase_since: 0,
+            });
+            initializations.push(msg);
+        }
+        for (index, msg) in initializations.into_iter().enumerate() {
+            network.broadcast(index, msg);
+        }
+        Ok(network)
+    }
+
+    pub fn now(&self) -> u64 {
+        self.now
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn peer_id(&self, node: usize) -> &PeerId {
+        &self.nodes[node].peer_id
+    }
+
+    pub fn key_gen(&self, node: usize) -> &KeyGen {
+        &self.nodes[node].key_gen
+    }
+
+    pub fn key_gen_mut(&mut self, node: usize) -> &mut KeyGen {
+        &mut self.nodes[node].key_gen
+    }
+
+    /// The messages sent so far by the `KeyGen` of each member, oldest first, before any
+    /// adversary tampered with them.
+    pub fn sent_messages(&self) -> &[(usize, Message)] {
+        &self.sent
+    }
+
+    /// The errors returned by members so far, oldest first.
+    pub fn errors(&self) -> &[SimError] {
+        &self.errors
+    }
+
+    /// Makes `node` malicious: from now on, everything it sends passes through `adversary`.
+    pub fn set_adversary(&mut self, node: usize, adversary: Box<dyn Adversary>) {
+        let _ = self.adversaries.insert(node, adversary);
+    }
+
+    /// Stops `node` from sending or receiving any further message.
+    pub fn crash(&mut self, node: usize) {
+        self.nodes[node].crashed = true;
+    }
+
+    /// Splits the network: from now on, only messages between members of the same group are
+    /// delivered, including those already in flight. Members missing from all groups are cut off.
+    pub fn partition(&mut self, groups: Vec<BTreeSet<usize>>) {
+        self.partitions = groups;
+    }
+
+    /// Reconnects all the partitions.
+    pub fn heal(&mut self) {
+        self.partitions.clear();
+    }
+
+    /// Whether all members that did not crash reached the `Finalization` phase.
+    pub fn all_finalized(&self) -> bool {
+        self.nodes
+            .iter()
+            .filter(|node| !node.crashed)
+            .all(|node| node.key_gen.is_finalized())
+    }
+
+    /// The time of the next delivery or phase timer, if any.
+    pub fn next_event_time(&self) -> Option<u64> {
+        let delivery = self.queue.keys().next().map(|(time, _)| *time);
+        let timer = self.next_timer().map(|(time, _)| time);
+        match (delivery, timer) {
+            (Some(delivery), Some(timer)) => Some(delivery.min(timer)),
+            (delivery, timer) => delivery.or(timer),
+        }
+    }
+
+    /// Advances to the next delivery or phase timer and processes it. Returns `false` if there
+    /// was none.
+    pub fn step(&mut self) -> bool {
+        let delivery = self.queue.keys().next().map(|(time, _)| *time);
+        match (delivery, self.next_timer()) {
+            (None, None) => false,
+            (Some(delivery), Some((timer, node))) if timer < delivery => {
+                self.fire_timer(timer, node);
+                true
+            }
+            (None, Some((timer, node))) => {
+                self.fire_timer(timer, node);
+                true
+            }
+            (Some(_), _) => {
+                self.deliver();
+                true
+            }
+        }
+    }
+
+    /// Processes all events due up to `deadline`, returning whether the network went idle.
+    pub fn run_until(&mut self, deadline: u64) -> bool {
+        while let Some(time) = self.next_event_time() {
+            if time > deadline {
+                return false;
+            }
+            let _ = self.step();
+        }
+        true
+    }
+
+    fn next_timer(&self) -> Option<(u64, usize)> {
+        self.nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, node)| {
+                !node.crashed
+                    && (node.phase == Phase::Contribution || node.phase == Phase::Complaining)
+            })
+            .map(|(index, node)| (node.phase_since + self.config.phase_timeout, index))
+            .min()
+    }
+
+    fn fire_timer(&mut self, time: u64, index: usize) {
+        self.now = time;
+        // Restarts the timer, in case the transition fails and the member stays in its phase.
+        self.nodes[index].phase_since = time;
+        let result = self.nodes[index]
+            .key_gen
+            .timed_phase_transition(&mut self.rng);
+        self.handle_result(index, result);
+    }
+
+    fn deliver(&mut self) {
+        let (time, seq) = match self.queue.keys().next() {
+            Some(key) => *key,
+            None => return,
+        };
+        let delivery = match self.queue.remove(&(time, seq)) {
+            Some(delivery) => delivery,
+            None => return,
+        };
+        self.now = time;
+        if self.nodes[delivery.to].crashed || !self.connected(delivery.from, delivery.to) {
+            return;
+        }
+        let result = self.nodes[delivery.to]
+            .key_gen
+            .handle_message(&mut self.rng, delivery.msg);
+        self.handle_result(delivery.to, result);
+    }
+
+    fn handle_result(&mut self, index: usize, result: Result<Vec<Message>, Error>) {
+        match result {
+            Ok(msgs) => {
+                for msg in msgs {
+                    self.broadcast(index, msg);
+                }
+            }
+            Err(error) => self.errors.push(SimError {
+                time: self.now,
+                node: index,
+                error,
+            }),
+        }
+        let node = &mut self.nodes[index];
+        if node.key_gen.phase() != node.phase {
+            node.phase = node.key_gen.phase();
+            node.phase_since = self.now;
+        }
+    }
+
+    fn broadcast(&mut self, from: usize, msg: Message) {
+        if self.nodes[from].crashed {
+            return;
+        }
+        self.sent.push((from, msg.clone()));
+        for to in 0..self.nodes.len() {
+            let msgs = match self.adversaries.get_mut(&from) {
+                Some(adversary) => adversary.tamper(&mut self.rng, to, &msg),
+                None => vec![msg.clone()],
+            };
+            for msg in msgs {
+                self.send(from, to, msg);
+            }
+        }
+    }
+
+    fn send(&mut self, from: usize, to: usize, msg: Message) {
+        if self.rng.gen_bool(self.config.drop_probability) {
+            return;
+        }
+        let copies = if self.rng.gen_bool(self.config.duplicate_probability) {
+            2
+        } else {
+            1
+        };
+        for _ in 0..copies {
+            let delay = self
+                .rng
+                .gen_range(self.config.min_delay, self.config.max_delay + 1);
+            let _ = self.queue.insert(
+                (self.now + delay, self.next_seq),
+                Delivery {
+                    from,
+                    to,
+                    msg: msg.clone(),
+                },
+            );
+            self.next_seq += 1;
+        }
+    }
+
+    fn connected(&self, from: usize, to: usize) -> bool {
+        self.partitions.is_empty()
+            || self
+                .partitions
+                .iter()
+                .any(|group| group.contains(&from) && group.contains(&to))
+    }
+}
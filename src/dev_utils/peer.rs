@@ -7,6 +7,9 @@
 // specific language governing permissions and limitations relating to use of the SAFE Network
 // Software.
 
+use crate::key_gen::identity::Identity;
+use crate::key_gen::rng_adapter::RngAdapter;
+use blsttc::{ff::Field, serde_impl::SerdeSecret, Fr, PublicKey, SecretKey};
 use rand::Rng;
 use serde_derive::{Deserialize, Serialize};
 use std::{
@@ -20,16 +23,49 @@ use xor_name::XorName;
 /// non-mocks, these two traits must be implemented by two separate types; a public key and secret
 /// key respectively.
 #[derive(Clone, Serialize, Deserialize)]
+#[serde(from = "StoredPeerId", into = "StoredPeerId")]
 pub struct PeerId {
     id: XorName,
     public_key: PublicKey,
     secret_key: SecretKey,
 }
 
+// The storage format of a `PeerId`, INCLUDING the secret key.
+#[derive(Serialize, Deserialize)]
+struct StoredPeerId {
+    id: XorName,
+    secret_key: SerdeSecret<SecretKey>,
+}
+
+impl From<StoredPeerId> for PeerId {
+    fn from(stored: StoredPeerId) -> Self {
+        let secret_key = stored.secret_key.into_inner();
+        Self {
+            id: stored.id,
+            public_key: secret_key.public_key(),
+            secret_key,
+        }
+    }
+}
+
+impl From<PeerId> for StoredPeerId {
+    fn from(peer_id: PeerId) -> Self {
+        Self {
+            id: peer_id.id,
+            secret_key: SerdeSecret(peer_id.secret_key),
+        }
+    }
+}
+
 impl PeerId {
     pub fn new() -> Self {
-        let (public_key, secret_key) = gen_keypair();
-        let mut rng = rand::thread_rng();
+        Self::with_rng(&mut rand::thread_rng())
+    }
+
+    /// Creates a mock ID drawn from `rng`, e.g. to get reproducible names from a seeded rng.
+    pub fn with_rng<R: Rng>(rng: &mut R) -> Self {
+        let secret_key = SecretKey::from_mut(&mut Fr::random(&mut RngAdapter(&mut *rng)));
+        let public_key = secret_key.public_key();
         Self {
             id: rng.gen(),
             public_key,
@@ -40,6 +76,25 @@ impl PeerId {
     pub fn name(&self) -> XorName {
         self.id
     }
+
+    pub fn public_key(&self) -> PublicKey {
+        self.public_key
+    }
+
+    /// Returns our identity among `peers`, holding their public keys by name.
+    pub fn identity(&self, peers: &[PeerId]) -> Identity {
+        let public_keys = peers
+            .iter()
+            .map(|peer| (peer.name(), peer.public_key))
+            .collect();
+        Identity::new(self.secret_key.clone(), public_keys)
+    }
+}
+
+impl Default for PeerId {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Debug for PeerId {
@@ -81,30 +136,3 @@ pub fn create_ids(count: usize) -> Vec<PeerId> {
     ids.sort();
     ids
 }
-
-const SIGNATURE_LENGTH: usize = 32;
-const KEY_LENGTH: usize = 32;
-
-// **NOT FOR PRODUCTION USE**: Mock public key.
-#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
-pub struct PublicKey([u8; KEY_LENGTH]);
-
-// **NOT FOR PRODUCTION USE**: Mock secret key.
-#[derive(Clone, Serialize, Deserialize)]
-pub struct SecretKey([u8; KEY_LENGTH]);
-
-// **NOT FOR PRODUCTION USE**: Mock signature.
-#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
-pub struct Signature([u8; SIGNATURE_LENGTH]);
-
-impl Debug for Signature {
-    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
-        write!(formatter, "Signature(..)")
-    }
-}
-
-fn gen_keypair() -> (PublicKey, SecretKey) {
-    let mut rng = rand::thread_rng();
-    let bytes: [u8; KEY_LENGTH] = rng.gen();
-    (PublicKey(bytes), SecretKey(bytes))
-}
@@ -0,0 +1,35 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// https://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+use super::Phase;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use xor_name::XorName;
+
+/// Why a member gave up on a DKG session, carried by `Message::Abort`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum AbortReason {
+    /// Too many members failed the Complaining phase, see `Error::TooManyNonVoters`.
+    TooManyNonVoters,
+    /// The session didn't complete in time, stuck in the given phase.
+    Timeout(Phase),
+    /// The member was asked to stop the session.
+    Cancelled,
+}
+
+/// The abort agreed by a quorum of members, see `KeyGen::abort_report`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AbortReport {
+    /// The reason given by most of the aborting members.
+    pub reason: AbortReason,
+    /// The members blamed alike by all the aborting members.
+    pub blamed: BTreeSet<XorName>,
+    /// The members who sent an `Abort` blaming them.
+    pub aborting: BTreeSet<XorName>,
+}
@@ -11,7 +11,7 @@ use super::Error;
 use aes::Aes128;
 use block_modes::block_padding::Pkcs7;
 use block_modes::{BlockMode, Cbc};
-use rand::Rng;
+use rand::{Rng, RngCore};
 use serde_derive::{Deserialize, Serialize};
 use std::collections::{BTreeMap, BTreeSet};
 use xor_name::XorName;
@@ -48,13 +48,11 @@ pub struct Encryptor {
 }
 
 impl Encryptor {
-    pub fn new(peers: &BTreeSet<XorName>) -> Self {
+    pub fn new<R: RngCore>(rng: &mut R, peers: &BTreeSet<XorName>) -> Self {
         let mut keys_map = BTreeMap::new();
-        // let mut rng = rand::thread_rng();
-        // let mut rng_adaptor = RngAdapter(&mut rng);
         for name in peers.iter() {
-            let key = Key(rand::thread_rng().gen());
-            let iv = Iv(rand::thread_rng().gen());
+            let key = Key(rng.gen());
+            let iv = Iv(rng.gen());
             let _ = keys_map.insert(*name, (key, iv));
         }
         Encryptor { keys_map }
@@ -0,0 +1,49 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// https://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+use super::abort::AbortReport;
+use super::{AcknowledgmentFault, PartFault, Phase};
+use blsttc::PublicKey;
+use xor_name::XorName;
+
+/// Describes the progress of a DKG session. Events are queued by `KeyGen` as they happen, and
+/// are drained with `KeyGen::drain_events`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DkgEvent {
+    /// We transited from one phase into another.
+    PhaseChanged { from: Phase, to: Phase },
+    /// We verified the row of the part proposed by `from`.
+    PartAccepted { from: u64 },
+    /// We missed the part proposed by `from`, and rebuilt our row from acknowledged values.
+    RowRecovered { from: u64 },
+    /// We verified the value `from` acknowledged for the part proposed by `for_part`.
+    AckAccepted { from: u64, for_part: u64 },
+    /// We complained against `target`.
+    ComplaintRaised {
+        target: u64,
+        reason: ComplaintReason,
+    },
+    /// The member got excluded by a quorum of complaints.
+    MemberExcluded(XorName),
+    /// The qualified set got agreed and we generated the keys of the group.
+    Finalized { pk: PublicKey },
+    /// A quorum of members aborted the session.
+    Aborted(AbortReport),
+}
+
+/// Why we complained against a member.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ComplaintReason {
+    /// The member sent us an invalid part.
+    Part(PartFault),
+    /// The member sent us an invalid acknowledgment.
+    Acknowledgment(AcknowledgmentFault),
+    /// The member didn't contribute a part during the Contribution phase.
+    NonContribution,
+}
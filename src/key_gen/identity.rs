@@ -0,0 +1,145 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// https://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+use super::sharexorname::ShareXorName;
+use super::Error;
+use bincode::serialize;
+use blsttc::{serde_impl::SerdeSecret, PublicKey, SecretKey, Signature};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{self, Debug, Formatter};
+use xor_name::XorName;
+
+/// The long-term key pair of a member, together with the public keys of the other members.
+///
+/// Messages that decide the outcome of a session, i.e. `Qualification` votes, `SyncResponse`s and
+/// `Abort`s, are signed with the secret key and only handled once the signature verifies
+/// against the public key of their sender.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "StoredIdentity", into = "StoredIdentity")]
+pub struct Identity {
+    secret_key: SecretKey,
+    public_keys: BTreeMap<XorName, PublicKey>,
+}
+
+// The storage format of an `Identity`, INCLUDING the secret key.
+#[derive(Serialize, Deserialize)]
+struct StoredIdentity {
+    secret_key: SerdeSecret<SecretKey>,
+    public_keys: BTreeMap<XorName, PublicKey>,
+}
+
+impl From<StoredIdentity> for Identity {
+    fn from(stored: StoredIdentity) -> Self {
+        Identity::new(stored.secret_key.into_inner(), stored.public_keys)
+    }
+}
+
+impl From<Identity> for StoredIdentity {
+    fn from(identity: Identity) -> Self {
+        StoredIdentity {
+            secret_key: SerdeSecret(identity.secret_key),
+            public_keys: identity.public_keys,
+        }
+    }
+}
+
+impl Identity {
+    /// Creates the identity of the member holding `secret_key`, given the public keys of the
+    /// members by name, ours included.
+    pub fn new(secret_key: SecretKey, public_keys: BTreeMap<XorName, PublicKey>) -> Self {
+        Self {
+            secret_key,
+            public_keys,
+        }
+    }
+
+    pub fn public_key(&self) -> PublicKey {
+        self.secret_key.public_key()
+    }
+
+    pub fn public_keys(&self) -> &BTreeMap<XorName, PublicKey> {
+        &self.public_keys
+    }
+
+    // Checks that every member has a public key, and that ours matches our secret key.
+    pub(crate) fn validate(&self, our_id: XorName, names: &BTreeSet<XorName>) -> Result<(), Error> {
+        if let Some(name) = names
+            .iter()
+            .find(|name| !self.public_keys.contains_key(name))
+        {
+            return Err(Error::MissingPublicKey(*name));
+        }
+        if self.public_keys.get(&our_id) != Some(&self.public_key()) {
+            return Err(Error::IdentityMismatch(our_id));
+        }
+        Ok(())
+    }
+
+    // Signs `content` on behalf of `signer`, binding it to the session and to the kind of vote.
+    pub(crate) fn sign<T: serde::Serialize>(
+        &self,
+        label: &'static str,
+        context: &ShareXorName,
+        signer: u64,
+        content: &T,
+    ) -> Result<Signature, Error> {
+        let msg = signed_content(label, context, signer, content)?;
+        Ok(self.secret_key.sign(msg))
+    }
+
+    // Verifies that the member of index `signer` in `context` signed `content`.
+    pub(crate) fn verify<T: serde::Serialize>(
+        &self,
+        label: &'static str,
+        context: &ShareXorName,
+        signer: u64,
+        content: &T,
+        signature: &Signature,
+    ) -> Result<(), Error> {
+        let signer_id = context
+            .get_xorname(signer)
+            .ok_or(Error::UnknownSender(signer))?;
+        let public_key = self
+            .public_keys
+            .get(&signer_id)
+            .ok_or(Error::MissingPublicKey(signer_id))?;
+        let msg = signed_content(label, context, signer, content)?;
+        if public_key.verify(signature, msg) {
+            Ok(())
+        } else {
+            Err(Error::InvalidSignature(signer))
+        }
+    }
+}
+
+impl Debug for Identity {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "Identity({:?}, {} public keys)",
+            self.public_key(),
+            self.public_keys.len()
+        )
+    }
+}
+
+fn signed_content<T: serde::Serialize>(
+    label: &'static str,
+    context: &ShareXorName,
+    signer: u64,
+    content: &T,
+) -> Result<Vec<u8>, Error> {
+    Ok(serialize(&(
+        label,
+        context.get_keygenid(),
+        signer,
+        content,
+    ))?)
+}
@@ -0,0 +1,147 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// https://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+//! Metrics recorded through the [`metrics`](https://docs.rs/metrics) facade, so that any
+//! exporter installed by the application picks them up. Without the `metrics` feature all
+//! functions in here are no-ops.
+//!
+//! | Name                                     | Type      | Labels           |
+//! |------------------------------------------|-----------|------------------|
+//! | `bls_dkg_rounds_started_total`           | counter   | `mode`           |
+//! | `bls_dkg_rounds_finished_total`          | counter   | `mode`, `result` |
+//! | `bls_dkg_phase_duration_seconds`         | histogram | `phase`          |
+//! | `bls_dkg_messages_received_total`        | counter   | `kind`           |
+//! | `bls_dkg_message_bytes_received_total`   | counter   | `kind`           |
+//! | `bls_dkg_messages_sent_total`            | counter   | `kind`           |
+//! | `bls_dkg_message_bytes_sent_total`       | counter   | `kind`           |
+//! | `bls_dkg_complaints_raised_total`        | counter   | `kind`, `reason` |
+//! | `bls_dkg_members_excluded_total`         | counter   | `kind`, `reason` |
+//! | `bls_dkg_pending_messages_dropped_total` | counter   | `kind`           |
+//!
+//! `result` is one of `finalized`, `too_many_non_voters`, `evicted` or `aborted`. A member excluded without
+//! us having complained against it is reported with the `unwitnessed` kind.
+
+#![cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+
+use super::event::ComplaintReason;
+use super::message::{Message, MessageKind};
+use super::mode::Mode;
+use super::Phase;
+use std::time::Duration;
+
+pub(crate) const RESULT_FINALIZED: &str = "finalized";
+pub(crate) const RESULT_TOO_MANY_NON_VOTERS: &str = "too_many_non_voters";
+pub(crate) const RESULT_EVICTED: &str = "evicted";
+pub(crate) const RESULT_ABORTED: &str = "aborted";
+
+pub(crate) fn round_started(mode: &Mode) {
+    #[cfg(feature = "metrics")]
+    metrics::increment_counter!("bls_dkg_rounds_started_total", "mode" => mode_label(mode));
+}
+
+pub(crate) fn round_finished(mode: &Mode, result: &'static str) {
+    #[cfg(feature = "metrics")]
+    metrics::increment_counter!(
+        "bls_dkg_rounds_finished_total",
+        "mode" => mode_label(mode),
+        "result" => result
+    );
+}
+
+pub(crate) fn phase_finished(phase: Phase, elapsed: Duration) {
+    #[cfg(feature = "metrics")]
+    metrics::histogram!(
+        "bls_dkg_phase_duration_seconds",
+        elapsed.as_secs_f64(),
+        "phase" => format!("{:?}", phase)
+    );
+}
+
+pub(crate) fn message_received(msg: &Message) {
+    #[cfg(feature = "metrics")]
+    {
+        let kind = format!("{:?}", msg.kind());
+        metrics::increment_counter!("bls_dkg_messages_received_total", "kind" => kind.clone());
+        metrics::counter!(
+            "bls_dkg_message_bytes_received_total",
+            serialized_size(msg),
+            "kind" => kind
+        );
+    }
+}
+
+pub(crate) fn messages_sent(msgs: &[Message]) {
+    #[cfg(feature = "metrics")]
+    for msg in msgs {
+        let kind = format!("{:?}", msg.kind());
+        metrics::increment_counter!("bls_dkg_messages_sent_total", "kind" => kind.clone());
+        metrics::counter!(
+            "bls_dkg_message_bytes_sent_total",
+            serialized_size(msg),
+            "kind" => kind
+        );
+    }
+}
+
+pub(crate) fn complaint_raised(reason: &ComplaintReason) {
+    #[cfg(feature = "metrics")]
+    {
+        let (kind, reason) = reason_labels(Some(reason));
+        metrics::increment_counter!(
+            "bls_dkg_complaints_raised_total",
+            "kind" => kind,
+            "reason" => reason
+        );
+    }
+}
+
+pub(crate) fn member_excluded(reason: Option<&ComplaintReason>) {
+    #[cfg(feature = "metrics")]
+    {
+        let (kind, reason) = reason_labels(reason);
+        metrics::increment_counter!(
+            "bls_dkg_members_excluded_total",
+            "kind" => kind,
+            "reason" => reason
+        );
+    }
+}
+
+pub(crate) fn pending_message_dropped(kind: MessageKind) {
+    #[cfg(feature = "metrics")]
+    metrics::increment_counter!(
+        "bls_dkg_pending_messages_dropped_total",
+        "kind" => format!("{:?}", kind)
+    );
+}
+
+#[cfg(feature = "metrics")]
+fn mode_label(mode: &Mode) -> &'static str {
+    // The recovered index is left out to keep the label cardinality bounded.
+    match mode {
+        Mode::Initial => "initial",
+        Mode::Refresh => "refresh",
+        Mode::Recovery(_) => "recovery",
+    }
+}
+
+#[cfg(feature = "metrics")]
+fn reason_labels(reason: Option<&ComplaintReason>) -> (&'static str, String) {
+    match reason {
+        Some(ComplaintReason::Part(fault)) => ("part", format!("{:?}", fault)),
+        Some(ComplaintReason::Acknowledgment(fault)) => ("acknowledgment", format!("{:?}", fault)),
+        Some(ComplaintReason::NonContribution) => ("non_contribution", String::new()),
+        None => ("unwitnessed", String::new()),
+    }
+}
+
+#[cfg(feature = "metrics")]
+fn serialized_size(msg: &Message) -> u64 {
+    bincode::serialized_size(msg).unwrap_or(0)
+}
@@ -7,10 +7,12 @@
 // specific language governing permissions and limitations relating to use of the SAFE Network
 // Software.
 
+use super::abort::AbortReason;
 use super::encryptor::{Iv, Key};
 use super::mode::Mode;
 use super::sharexorname::ShareXorName;
-use super::{Acknowledgment, Part};
+use super::{Acknowledgment, Part, Phase};
+use blsttc::{Signature, SignatureShare};
 use serde_derive::{Deserialize, Serialize};
 use std::collections::{BTreeMap, BTreeSet};
 use std::fmt;
@@ -49,6 +51,76 @@ pub enum Message {
         context: ShareXorName,
         ack: Acknowledgment,
     },
+    /// Votes for the set of proposers whose parts the sender holds complete, signed with the
+    /// identity of the sender.
+    Qualification {
+        key_gen_id: u64,
+        context: ShareXorName,
+        qual: BTreeSet<u64>,
+        signature: Signature,
+    },
+    Finalized {
+        key_gen_id: u64,
+        context: ShareXorName,
+        public_key_set_digest: [u8; 32],
+        signature_share: SignatureShare,
+    },
+    /// Asks the members for messages we missed, to be multicast.
+    ResendRequest {
+        key_gen_id: u64,
+        context: ShareXorName,
+        what: Resend,
+    },
+    /// Asks the members for everything needed to join the session late, to be multicast.
+    SyncRequest {
+        key_gen_id: u64,
+        context: ShareXorName,
+    },
+    /// Answers a `SyncRequest` with the phase of the sender and the messages of the sender that
+    /// the requester needs, bound together by their digest, which the sender signs.
+    SyncResponse {
+        key_gen_id: u64,
+        context: ShareXorName,
+        requester: u64,
+        phase: Phase,
+        messages: Vec<Message>,
+        digest: [u8; 32],
+        signature: Signature,
+    },
+    /// Gives up on the session, blaming the given members, signed by the sender. The session is
+    /// aborted once a quorum of members sent one, see `KeyGen::abort_report`.
+    Abort {
+        key_gen_id: u64,
+        context: ShareXorName,
+        reason: AbortReason,
+        blamed: BTreeSet<u64>,
+        signature: Signature,
+    },
+}
+
+/// What a `ResendRequest` asks for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Resend {
+    /// The `Proposal` of the given proposer that is addressed to the requester.
+    Proposal(u64),
+    /// The `Acknowledgment`s addressed to the requester for the part of the given proposer.
+    Acks(u64),
+}
+
+/// The type of a `Message`, without its content.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum MessageKind {
+    Initialization,
+    Proposal,
+    Complaint,
+    Justification,
+    Acknowledgment,
+    Qualification,
+    Finalized,
+    ResendRequest,
+    SyncRequest,
+    SyncResponse,
+    Abort,
 }
 
 impl fmt::Debug for Message {
@@ -73,11 +145,74 @@ impl fmt::Debug for Message {
             Message::Acknowledgment { key_gen_id, .. } => {
                 write!(formatter, "Acknowledgment({})", key_gen_id)
             }
+            Message::Qualification {
+                key_gen_id, qual, ..
+            } => write!(formatter, "Qualification({} - {:?})", key_gen_id, qual),
+            Message::Finalized { key_gen_id, .. } => write!(formatter, "Finalized({})", key_gen_id),
+            Message::ResendRequest {
+                key_gen_id, what, ..
+            } => write!(formatter, "ResendRequest({} - {:?})", key_gen_id, what),
+            Message::SyncRequest { key_gen_id, .. } => {
+                write!(formatter, "SyncRequest({})", key_gen_id)
+            }
+            Message::SyncResponse {
+                key_gen_id,
+                phase,
+                messages,
+                ..
+            } => write!(
+                formatter,
+                "SyncResponse({} - {:?} - {:?})",
+                key_gen_id, phase, messages
+            ),
+            Message::Abort {
+                key_gen_id,
+                reason,
+                blamed,
+                ..
+            } => write!(
+                formatter,
+                "Abort({} - {:?} - {:?})",
+                key_gen_id, reason, blamed
+            ),
         }
     }
 }
 
 impl Message {
+    pub fn kind(&self) -> MessageKind {
+        match self {
+            Message::Initialization { .. } => MessageKind::Initialization,
+            Message::Proposal { .. } => MessageKind::Proposal,
+            Message::Complaint { .. } => MessageKind::Complaint,
+            Message::Justification { .. } => MessageKind::Justification,
+            Message::Acknowledgment { .. } => MessageKind::Acknowledgment,
+            Message::Qualification { .. } => MessageKind::Qualification,
+            Message::Finalized { .. } => MessageKind::Finalized,
+            Message::ResendRequest { .. } => MessageKind::ResendRequest,
+            Message::SyncRequest { .. } => MessageKind::SyncRequest,
+            Message::SyncResponse { .. } => MessageKind::SyncResponse,
+            Message::Abort { .. } => MessageKind::Abort,
+        }
+    }
+
+    /// The index of the sender.
+    pub fn key_gen_id(&self) -> u64 {
+        match self {
+            Message::Initialization { key_gen_id, .. }
+            | Message::Proposal { key_gen_id, .. }
+            | Message::Complaint { key_gen_id, .. }
+            | Message::Justification { key_gen_id, .. }
+            | Message::Acknowledgment { key_gen_id, .. }
+            | Message::Qualification { key_gen_id, .. }
+            | Message::Finalized { key_gen_id, .. }
+            | Message::ResendRequest { key_gen_id, .. }
+            | Message::SyncRequest { key_gen_id, .. }
+            | Message::SyncResponse { key_gen_id, .. }
+            | Message::Abort { key_gen_id, .. } => *key_gen_id,
+        }
+    }
+
     pub fn get_context(&self) -> &ShareXorName {
         match &self {
             Message::Initialization {
@@ -109,6 +244,43 @@ impl Message {
                 context,
                 ack: _,
             } => context,
+            Message::Qualification {
+                key_gen_id: _,
+                context,
+                qual: _,
+                signature: _,
+            } => context,
+            Message::Finalized {
+                key_gen_id: _,
+                context,
+                public_key_set_digest: _,
+                signature_share: _,
+            } => context,
+            Message::ResendRequest {
+                key_gen_id: _,
+                context,
+                what: _,
+            } => context,
+            Message::SyncRequest {
+                key_gen_id: _,
+                context,
+            } => context,
+            Message::SyncResponse {
+                key_gen_id: _,
+                context,
+                requester: _,
+                phase: _,
+                messages: _,
+                digest: _,
+                signature: _,
+            } => context,
+            Message::Abort {
+                key_gen_id: _,
+                context,
+                reason: _,
+                blamed: _,
+                signature: _,
+            } => context,
         }
     }
     pub fn get_keygenid(&self) -> [u8; 32] {
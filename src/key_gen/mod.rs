@@ -7,16 +7,28 @@
 // specific language governing permissions and limitations relating to use of the SAFE Network
 // Software.
 
+pub mod abort;
 mod encryptor;
+pub mod event;
+pub mod identity;
+mod instrumentation;
 pub mod message;
 pub mod mode;
 pub mod outcome;
-mod rng_adapter;
+pub mod params;
+mod pending;
+pub mod policy;
+pub mod progress;
+pub(crate) mod rng_adapter;
 pub mod sharexorname;
+#[cfg(any(test, feature = "test-utils"))]
+pub(crate) mod tamper;
+pub mod transcript;
 
 #[cfg(test)]
 mod tests;
 
+use abort::{AbortReason, AbortReport};
 use base64;
 use bincode::{self, deserialize, serialize};
 use blsttc::{
@@ -24,13 +36,19 @@ use blsttc::{
     group::CurveAffine,
     poly::{BivarCommitment, BivarPoly, Poly},
     serde_impl::FieldWrap,
-    Fr, G1Affine, IntoFr,
+    Fr, G1Affine, IntoFr, SecretKey,
 };
-pub use blsttc::{PublicKeySet, SecretKeyShare};
+pub use blsttc::{PublicKey, PublicKeySet, SecretKeyShare, Signature, SignatureShare};
 use encryptor::{Encryptor, Iv, Key};
-use message::Message;
+use event::{ComplaintReason, DkgEvent};
+use identity::Identity;
+use message::{Message, MessageKind, Resend};
 use mode::Mode;
-use outcome::Outcome;
+use outcome::{ConfirmedOutcome, Outcome};
+use params::DkgParams;
+use pending::PendingMessages;
+use policy::{DkgPolicy, Quorum};
+use progress::DkgProgress;
 use rand::{self, RngCore};
 use serde_derive::{Deserialize, Serialize};
 use sharexorname::ShareXorName;
@@ -39,25 +57,40 @@ use std::iter::FromIterator;
 use std::{
     fmt::{self, Debug, Formatter},
     mem,
+    time::Instant,
 };
 use xor_name::XorName;
 
+// How many times we answer the `SyncRequest`s of a member during a round. We answer this many of
+// its `ResendRequest`s per member, as it may ask for the part of each of them.
+const MAX_RESENDS: usize = 3;
+
+// Identifies one of our messages kept to be sent again: its kind, then the proposer and the
+// receiver of the part it carries or acknowledges, or our index twice for our `Initialization`.
+type SentKey = (MessageKind, u64, u64);
+
+// What the signatures of `Qualification` votes, `SyncResponse`s and `Abort`s sign, see
+// `Identity::sign`.
+const QUALIFICATION_LABEL: &str = "qualification";
+const SYNC_RESPONSE_LABEL: &str = "sync-response";
+const ABORT_LABEL: &str = "abort";
+
 /// A local error while handling a message, that was not caused by that message being invalid.
 #[non_exhaustive]
 #[derive(Clone, Eq, thiserror::Error, PartialEq, Debug)]
 pub enum Error {
-    /// Unknown error.
-    #[error("Unknown")]
-    Unknown,
-    /// Unknown sender.
-    #[error("Unknown sender")]
-    UnknownSender,
+    /// The sender index is not a member of the current context.
+    #[error("Unknown sender {0}")]
+    UnknownSender(u64),
+    /// The targeted index is not a member of the current context.
+    #[error("Unknown target {0}")]
+    UnknownTarget(u64),
+    /// We got excluded from the DKG session by the other members.
+    #[error("Evicted from the DKG session")]
+    EvictedSelf,
     /// Failed to serialize message.
     #[error("Serialization error: {}", _0)]
     Serialization(String),
-    /// Network error from Quic-P2P.
-    #[error("QuicP2P error: {}", _0)]
-    QuicP2P(String),
     /// Failed to encrypt message.
     #[error("Encryption error")]
     Encryption,
@@ -70,12 +103,64 @@ pub enum Error {
     /// Mismatched context.
     #[error("Mismatched context")]
     ContextMismatch {
-        expected: ShareXorName,
-        actual: ShareXorName,
+        expected: Box<ShareXorName>,
+        actual: Box<ShareXorName>,
     },
     /// Ack on a missed part.
     #[error("ACK on missed part")]
     MissingPart,
+    /// No qualified set of proposers has been agreed yet.
+    #[error("Qualified set not agreed")]
+    QualNotAgreed,
+    /// The agreed qualified set contains proposers we don't hold a complete part for.
+    #[error("Qualified set mismatch")]
+    QualMismatch {
+        agreed: BTreeSet<u64>,
+        local: BTreeSet<u64>,
+    },
+    /// The threshold doesn't suit the number of members.
+    #[error("Invalid threshold {t} for {n} members, 0 < t < n and 2t >= n are required")]
+    InvalidThreshold { t: usize, n: usize },
+    /// We are not a member of the DKG session.
+    #[error("{0} is not a member")]
+    NotAMember(XorName),
+    /// The member names differ from the names of the context.
+    #[error("Member names don't match the context")]
+    MembersMismatch {
+        names: BTreeSet<XorName>,
+        context: BTreeSet<XorName>,
+    },
+    /// The context lists a member more than once.
+    #[error("Duplicate member {0} in the context")]
+    DuplicateMember(XorName),
+    /// The context assigns a share to more than one member.
+    #[error("Duplicate share {0} in the context")]
+    DuplicateShare(u64),
+    /// The messages of a `SyncResponse` don't match its digest.
+    #[error("Sync response of {0} doesn't match its digest")]
+    SyncDigestMismatch(u64),
+    /// A rule of the policy requires a number of members that doesn't suit the session.
+    #[error("The {rule} quorum of {required} doesn't suit {n} members")]
+    InvalidQuorum {
+        rule: &'static str,
+        required: usize,
+        n: usize,
+    },
+    /// Too few members are left to run another session.
+    #[error("{n} members left, at least {min} are required")]
+    CommitteeTooSmall { n: usize, min: usize },
+    /// The parameters of a session lack the identity of the member.
+    #[error("Missing identity")]
+    MissingIdentity,
+    /// The identity lacks the public key of a member.
+    #[error("Missing public key of {0}")]
+    MissingPublicKey(XorName),
+    /// Our secret key doesn't match the public key listed for us.
+    #[error("The identity doesn't match the public key of {0}")]
+    IdentityMismatch(XorName),
+    /// The signature of a vote doesn't verify against the public key of its sender.
+    #[error("Invalid signature of {0}")]
+    InvalidSignature(u64),
 }
 
 impl From<Box<bincode::ErrorKind>> for Error {
@@ -98,6 +183,8 @@ pub struct Part {
     ser_row: Vec<u8>,
     // Encrypted rows from the sender.
     enc_rows: Vec<Vec<u8>>,
+    // Proof of knowledge of the constant term of our poly, i.e. a signature made with it.
+    proof: Signature,
 }
 
 impl Debug for Part {
@@ -180,6 +267,29 @@ impl<'a> serde::Deserialize<'a> for ProposalState {
     }
 }
 
+/// What we know of a `Part` addressed to us that we did not receive, to rebuild our row from the
+/// values the other members acknowledge to us.
+///
+/// The encrypted values of an `Acknowledgment` are of no help here, as they are encrypted with
+/// keys only the acknowledging member knows until it justifies itself. Instead, the commitment is
+/// taken from a `Part` of the same proposer addressed to another member, and each value is
+/// verified against it before use.
+struct RowRecovery {
+    commitment: BivarCommitment,
+    /// The verified values of our row, by index of the acknowledging member plus one.
+    values: BTreeMap<u64, Fr>,
+}
+
+impl RowRecovery {
+    // The state of the part, counting the values as verified acknowledgments.
+    fn into_proposal_state(self) -> ProposalState {
+        let mut state = ProposalState::new(self.commitment);
+        state.acks = self.values.keys().map(|index| index - 1).collect();
+        state.values = self.values;
+        state
+    }
+}
+
 /// The outcome of handling and verifying a `Part` message.
 pub enum PartOutcome {
     /// The message was valid: the part of it that was encrypted to us matched the public
@@ -190,7 +300,7 @@ pub enum PartOutcome {
     Invalid(PartFault),
 }
 
-#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Eq, PartialEq, PartialOrd, Ord)]
 pub enum Phase {
     Initialization,
     Contribution,
@@ -200,59 +310,96 @@ pub enum Phase {
     Finalization,
 }
 
-//#[derive(Default)]
+// The parameters of a DKG session carried by an `Initialization` message.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct InitializationParams {
+    // Following the `m of n` terminology, here m is the threshold and n is the total number.
+    m: usize,
+    n: usize,
+    member_list: BTreeSet<XorName>,
+    context: ShareXorName,
+    mode: Mode,
+}
+
 struct InitializationAccumulator {
     senders: BTreeSet<u64>,
     context: ShareXorName,
-    initializations: BTreeMap<(usize, usize, BTreeSet<XorName>), usize>,
+    quorum: Quorum,
+    // The parameters proposed by each sender of our context.
+    votes: BTreeMap<u64, InitializationParams>,
+    initializations: BTreeMap<InitializationParams, usize>,
+    // Senders that proposed a session of another context.
+    foreign_senders: BTreeSet<u64>,
+    agreed: Option<InitializationParams>,
 }
 
 impl InitializationAccumulator {
-    fn new() -> InitializationAccumulator {
+    fn new(context: ShareXorName, quorum: Quorum) -> InitializationAccumulator {
         InitializationAccumulator {
             senders: BTreeSet::new(),
-            context: ShareXorName::from_xornames(Vec::new()), // may need to know this, look at callsite
+            context,
+            quorum,
+            votes: BTreeMap::new(),
             initializations: BTreeMap::new(),
+            foreign_senders: BTreeSet::new(),
+            agreed: None,
         }
     }
 
+    // Records the parameters proposed by `sender`, returning them once a quorum of senders
+    // proposed the very same ones. Proposals of another context are only recorded as conflicting, so that
+    // they can't take the place of the sender's proposal for our context.
     fn add_initialization(
         &mut self,
-        // Following the `m of n` terminology, here m is the threshold and n is the total number.
-        m: usize,
-        n: usize,
         sender: u64,
-        member_list: BTreeSet<XorName>,
-    ) -> Option<(usize, usize, BTreeSet<XorName>)> {
+        params: InitializationParams,
+    ) -> Option<InitializationParams> {
+        if params.context != self.context {
+            let _ = self.foreign_senders.insert(sender);
+            return None;
+        }
         if !self.senders.insert(sender) {
             return None;
         }
 
-        let paras = (m, n, member_list);
-        let value = self.initializations.entry(paras.clone()).or_insert(0);
+        let _ = self.votes.insert(sender, params.clone());
+        let value = self.initializations.entry(params.clone()).or_insert(0);
         *value += 1;
 
-        if *value >= m {
-            Some(paras)
+        if *value >= self.quorum.required(params.n, params.m) && self.agreed.is_none() {
+            self.agreed = Some(params.clone());
+            Some(params)
         } else {
             None
         }
     }
+
+    // Returns the senders whose proposal differs from `expected`.
+    fn conflicts(&self, expected: &InitializationParams) -> BTreeSet<u64> {
+        self.votes
+            .iter()
+            .filter(|(_, params)| *params != expected)
+            .map(|(sender, _)| *sender)
+            .chain(self.foreign_senders.iter().cloned())
+            .collect()
+    }
 }
 
 #[derive(Default)]
 struct ComplaintsAccumulator {
     names: BTreeSet<XorName>,
     threshold: usize,
+    policy: DkgPolicy,
     // Indexed by complaining targets.
     complaints: BTreeMap<XorName, BTreeSet<XorName>>,
 }
 
 impl ComplaintsAccumulator {
-    fn new(names: BTreeSet<XorName>, threshold: usize) -> ComplaintsAccumulator {
+    fn new(names: BTreeSet<XorName>, threshold: usize, policy: DkgPolicy) -> ComplaintsAccumulator {
         ComplaintsAccumulator {
             names,
             threshold,
+            policy,
             complaints: BTreeMap::new(),
         }
     }
@@ -268,9 +415,9 @@ impl ComplaintsAccumulator {
                 let _ = entry.get_mut().insert(sender_id);
             }
             Entry::Vacant(entry) => {
-                let mut targets = BTreeSet::new();
-                let _ = targets.insert(target_id);
-                let _ = entry.insert(targets);
+                let mut accusers = BTreeSet::new();
+                let _ = accusers.insert(sender_id);
+                let _ = entry.insert(accusers);
             }
         }
     }
@@ -284,8 +431,9 @@ impl ComplaintsAccumulator {
         // If missed too many times, such member shall be considered as invalid directly.
         let mut counts: BTreeMap<XorName, usize> = BTreeMap::new();
 
+        let n = self.names.len();
         for (target_id, accusers) in self.complaints.iter() {
-            if accusers.len() > self.names.len() - self.threshold {
+            if accusers.len() >= self.policy.exclusion.required(n, self.threshold) {
                 let _ = invalid_peers.insert(*target_id);
                 for peer in self.names.iter() {
                     if !accusers.contains(peer) {
@@ -295,7 +443,7 @@ impl ComplaintsAccumulator {
             }
         }
         for (peer, times) in counts {
-            if times > self.names.len() / 2 {
+            if times >= self.policy.missed_complaints.required(n, self.threshold) {
                 let _ = invalid_peers.insert(peer);
             }
         }
@@ -304,6 +452,111 @@ impl ComplaintsAccumulator {
     }
 }
 
+#[derive(Default)]
+struct QualificationAccumulator {
+    // Indexed by voters.
+    votes: BTreeMap<u64, BTreeSet<u64>>,
+}
+
+impl QualificationAccumulator {
+    fn add_vote(&mut self, sender: u64, qual: BTreeSet<u64>) {
+        let _ = self.votes.entry(sender).or_insert(qual);
+    }
+
+    // Returns the qualified set that at least `quorum` voters agreed on, if any.
+    fn agreed(&self, quorum: usize) -> Option<&BTreeSet<u64>> {
+        let mut tally: BTreeMap<&BTreeSet<u64>, usize> = BTreeMap::new();
+        for qual in self.votes.values() {
+            let count = tally.entry(qual).or_insert(0);
+            *count += 1;
+            if *count >= quorum {
+                return Some(qual);
+            }
+        }
+        None
+    }
+}
+
+#[derive(Default)]
+struct AbortAccumulator {
+    // Indexed by aborting members.
+    votes: BTreeMap<u64, (AbortReason, BTreeSet<u64>)>,
+}
+
+impl AbortAccumulator {
+    fn add_vote(&mut self, sender: u64, reason: AbortReason, blamed: BTreeSet<u64>) {
+        let _ = self.votes.entry(sender).or_insert((reason, blamed));
+    }
+
+    // Returns the members blamed alike by at least `quorum` votes, together with the reason
+    // given by most of these votes and their senders. With `quorum` a majority of the members, a
+    // single set of blamed members can be agreed, whichever votes each member received.
+    fn agreed(&self, quorum: usize) -> Option<(AbortReason, BTreeSet<u64>, BTreeSet<u64>)> {
+        let mut by_blamed: BTreeMap<&BTreeSet<u64>, BTreeMap<u64, AbortReason>> = BTreeMap::new();
+        for (sender, (reason, blamed)) in self.votes.iter() {
+            let _ = by_blamed
+                .entry(blamed)
+                .or_default()
+                .insert(*sender, *reason);
+        }
+        let (blamed, votes) = by_blamed
+            .into_iter()
+            .find(|(_, votes)| votes.len() >= quorum.max(1))?;
+        let mut reasons: BTreeMap<AbortReason, usize> = BTreeMap::new();
+        for reason in votes.values() {
+            *reasons.entry(*reason).or_insert(0) += 1;
+        }
+        // `max_by_key` returns the last of the tied reasons, i.e. the first one in order.
+        let (reason, _) = reasons.into_iter().rev().max_by_key(|(_, count)| *count)?;
+        Some((reason, blamed.clone(), votes.keys().copied().collect()))
+    }
+}
+
+#[derive(Default)]
+struct ConfirmationAccumulator {
+    // Whether we already multicast our own signature share.
+    sent: bool,
+    // Indexed by signers.
+    shares: BTreeMap<u64, ([u8; 32], SignatureShare)>,
+    // The combined group signature, once enough valid shares collected.
+    signature: Option<Signature>,
+}
+
+impl ConfirmationAccumulator {
+    fn add_share(&mut self, sender: u64, digest: [u8; 32], share: SignatureShare) {
+        let _ = self.shares.entry(sender).or_insert((digest, share));
+    }
+
+    // Combines the shares signing the same public key set as ours, once there are more than
+    // threshold of them.
+    fn try_combine(&mut self, public_key_set: &PublicKeySet, digest: [u8; 32]) {
+        if self.signature.is_some() {
+            return;
+        }
+        let public_key = public_key_set.public_key();
+        let msg = public_key.to_bytes();
+        let valid_shares: BTreeMap<u64, &SignatureShare> = self
+            .shares
+            .iter()
+            .filter(|(index, (share_digest, share))| {
+                *share_digest == digest
+                    && public_key_set
+                        .public_key_share(**index)
+                        .verify(share, &msg[..])
+            })
+            .map(|(index, (_, share))| (*index, share))
+            .collect();
+        if valid_shares.len() <= public_key_set.threshold() {
+            return;
+        }
+        if let Ok(signature) = public_key_set.combine_signatures(valid_shares) {
+            if public_key.verify(&signature, &msg[..]) {
+                self.signature = Some(signature);
+            }
+        }
+    }
+}
+
 /// An algorithm for dealerless distributed key generation.
 ///
 /// This is trying to follow the protocol as suggested at
@@ -318,13 +571,20 @@ impl ComplaintsAccumulator {
 /// 4. Call [`timed_phase_transition`](Self::timed_phase_transition) to complete the complaining
 ///    phase.
 /// 5. Repeat step 3 when there is incoming `Message`.
-/// 6. Call [`generate_keys`](Self::generate_keys) to get the public-key set and secret-key share,
-///    if the procedure finalized.
+/// 6. Once finalized, each node multicasts a `Qualification` vote on the set of proposers whose
+///    parts it considers complete. Keep handling the incoming votes as in step 3.
+/// 7. Call [`generate_keys`](Self::generate_keys) to get the public-key set and secret-key share,
+///    once the procedure finalized and a quorum agreed on the qualified set.
+/// 8. Each node then multicasts a `Finalized` message signing the new group public key with its
+///    new secret-key share. Keep handling them until
+///    [`confirmed_outcome`](Self::confirmed_outcome) returns the outcome with the group signature.
 pub struct KeyGen {
     /// Our node ID.
     our_id: XorName,
     /// Our node index.
     our_index: u64,
+    /// Our key pair, signing our votes, and the public keys verifying the votes of the members.
+    identity: Identity,
     /// The context matching indices with XorNames
     context: ShareXorName,
     /// The names of all nodes, by node ID.
@@ -333,98 +593,173 @@ pub struct KeyGen {
     encryptor: Encryptor,
     /// Proposed bivariate polynomials.
     parts: BTreeMap<u64, ProposalState>,
+    /// Rows we are rebuilding, by proposer, as we missed the `Part` addressed to us.
+    row_recoveries: BTreeMap<u64, RowRecovery>,
     /// The degree of the generated polynomial.
     threshold: usize,
     /// Current DKG phase.
     phase: Phase,
+    /// When the current phase was entered.
+    phase_started: Instant,
     /// Accumulates initializations.
     initalization_accumulator: InitializationAccumulator,
     /// Accumulates complaints.
     complaints_accumulator: ComplaintsAccumulator,
+    /// Accumulates votes on the qualified set of proposers.
+    qualification_accumulator: QualificationAccumulator,
+    /// Accumulates signature shares over the generated public key.
+    confirmation_accumulator: ConfirmationAccumulator,
+    /// Accumulates votes to abort the session.
+    abort_accumulator: AbortAccumulator,
     /// Pending complain messages.
     pending_complain_messages: Vec<Message>,
+    /// Why we complained against each member during the current round.
+    complaint_reasons: BTreeMap<XorName, ComplaintReason>,
     /// Pending messages that cannot handle yet.
-    pending_messages: Vec<Message>,
+    pending_messages: PendingMessages,
+    /// Our `Initialization`, `Proposal` and `Acknowledgment` messages of the current round, to
+    /// answer `ResendRequest`s and `SyncRequest`s.
+    sent_messages: BTreeMap<SentKey, Message>,
+    /// How many `ResendRequest`s of each member we answered during the current round.
+    resends: BTreeMap<u64, usize>,
+    /// How many times we answered the `SyncRequest`s of each member during the current round.
+    syncs: BTreeMap<u64, usize>,
+    /// Whether we recorded how the current round finished.
+    round_finished: bool,
     mode: Mode, // sharezero
+    /// The quorum and exclusion rules of the session.
+    policy: DkgPolicy,
+    /// Events not yet drained by the caller.
+    events: Vec<DkgEvent>,
 }
 
 impl KeyGen {
     /// Creates a new `KeyGen` instance, together with the `Initial` message that should be
     /// multicast to all nodes.
-    pub fn initialize(
+    pub fn initialize<R: RngCore>(
+        rng: &mut R,
         our_id: XorName,
         context: ShareXorName,
         threshold: usize,
         names: BTreeSet<XorName>,
         mode: Mode,
+        identity: Identity,
     ) -> Result<(KeyGen, Message), Error> {
-        if names.len() < threshold {
-            return Err(Error::Unknown);
-        }
-
-        let our_index = if let Some(index) = context.get_share(our_id) {
-            index as u64
-        } else {
-            return Err(Error::Unknown);
-        };
+        let params = DkgParams::builder(our_id, context, threshold)
+            .names(names)
+            .mode(mode)
+            .identity(identity)
+            .build()?;
+        Self::with_params(rng, params)
+    }
 
-        let key_gen = KeyGen {
+    /// Creates a new `KeyGen` instance from validated parameters, together with the `Initial`
+    /// message that should be multicast to all nodes.
+    pub fn with_params<R: RngCore>(
+        rng: &mut R,
+        params: DkgParams,
+    ) -> Result<(KeyGen, Message), Error> {
+        let our_id = params.our_id();
+        let context = params.context().clone();
+        let threshold = params.threshold();
+        let names = params.names().clone();
+        let mode = params.mode().clone();
+        let policy = *params.policy();
+        let our_index = context.get_share(our_id).ok_or(Error::NotAMember(our_id))?;
+
+        let mut key_gen = KeyGen {
             our_id,
             our_index,
+            identity: params.identity().clone(),
             context: context.clone(),
             names: names.clone(),
-            encryptor: Encryptor::new(&names),
+            encryptor: Encryptor::new(rng, &names),
             parts: BTreeMap::new(),
+            row_recoveries: BTreeMap::new(),
             threshold,
             phase: Phase::Initialization,
-            initalization_accumulator: InitializationAccumulator::new(),
-            complaints_accumulator: ComplaintsAccumulator::new(names.clone(), threshold),
+            phase_started: Instant::now(),
+            initalization_accumulator: InitializationAccumulator::new(
+                context.clone(),
+                policy.initialization,
+            ),
+            complaints_accumulator: ComplaintsAccumulator::new(names.clone(), threshold, policy),
+            qualification_accumulator: QualificationAccumulator::default(),
+            confirmation_accumulator: ConfirmationAccumulator::default(),
+            abort_accumulator: AbortAccumulator::default(),
             pending_complain_messages: Vec::new(),
-            pending_messages: Vec::new(),
+            complaint_reasons: BTreeMap::new(),
+            pending_messages: pending_messages(names.len()),
+            sent_messages: BTreeMap::new(),
+            resends: BTreeMap::new(),
+            syncs: BTreeMap::new(),
+            round_finished: false,
             mode: mode.clone(), //is_refresh: sharezero,
+            policy,
+            events: Vec::new(),
         };
 
-        Ok((
-            key_gen,
-            Message::Initialization {
-                key_gen_id: our_index,
-                context: context,
-                m: threshold,
-                n: names.len(),
-                member_list: names,
-                mode: mode, //sharezero: sharezero,
-            },
-        ))
+        instrumentation::round_started(&mode);
+        let msg = Message::Initialization {
+            key_gen_id: our_index,
+            context: context,
+            m: threshold,
+            n: names.len(),
+            member_list: names,
+            mode: mode, //sharezero: sharezero,
+        };
+        let _ = key_gen.sent_messages.insert(
+            (MessageKind::Initialization, our_index, our_index),
+            msg.clone(),
+        );
+        instrumentation::messages_sent(std::slice::from_ref(&msg));
+        Ok((key_gen, msg))
     }
 
     /// Creates a new `KeyGen` instance with specified data, e.g. for use after a recovery.
-    pub fn initialize_as_final(
+    pub fn initialize_as_final<R: RngCore>(
+        rng: &mut R,
         our_id: XorName,
         context: ShareXorName,
         threshold: usize,
+        identity: Identity,
     ) -> Result<KeyGen, Error> {
-        let our_index = if let Some(index) = context.get_share(our_id) {
-            index as u64
-        } else {
-            return Err(Error::Unknown);
-        };
+        let our_index = context.get_share(our_id).ok_or(Error::NotAMember(our_id))?;
 
         let names: BTreeSet<XorName> = BTreeSet::from_iter(context.clone().xornames);
+        identity.validate(our_id, &names)?;
+        let policy = DkgPolicy::default();
 
         let key_gen = KeyGen {
             our_id,
             our_index,
+            identity,
             context: context.clone(),
             names: names.clone(),
-            encryptor: Encryptor::new(&names),
+            encryptor: Encryptor::new(rng, &names),
             parts: BTreeMap::new(),
+            row_recoveries: BTreeMap::new(),
             threshold,
             phase: Phase::Finalization,
-            initalization_accumulator: InitializationAccumulator::new(),
-            complaints_accumulator: ComplaintsAccumulator::new(names.clone(), threshold),
+            phase_started: Instant::now(),
+            initalization_accumulator: InitializationAccumulator::new(
+                context.clone(),
+                policy.initialization,
+            ),
+            complaints_accumulator: ComplaintsAccumulator::new(names.clone(), threshold, policy),
+            qualification_accumulator: QualificationAccumulator::default(),
+            confirmation_accumulator: ConfirmationAccumulator::default(),
+            abort_accumulator: AbortAccumulator::default(),
             pending_complain_messages: Vec::new(),
-            pending_messages: Vec::new(),
+            complaint_reasons: BTreeMap::new(),
+            pending_messages: pending_messages(names.len()),
+            sent_messages: BTreeMap::new(),
+            resends: BTreeMap::new(),
+            syncs: BTreeMap::new(),
+            round_finished: false,
             mode: Mode::Initial, //is_refresh: sharezero,
+            policy,
+            events: Vec::new(),
         };
 
         Ok(key_gen)
@@ -452,37 +787,91 @@ impl KeyGen {
         rng: &mut R,
         msg: Message,
     ) -> Result<Vec<Message>, Error> {
-        if self.is_finalized() {
+        instrumentation::message_received(&msg);
+        // Qualification votes and confirmations keep arriving after we finalized, as well as
+        // requests of members still missing our messages.
+        if self.is_finalized()
+            && !matches!(
+                msg,
+                Message::Qualification { .. }
+                    | Message::Finalized { .. }
+                    | Message::ResendRequest { .. }
+                    | Message::SyncRequest { .. }
+            )
+        {
+            return Ok(Vec::new());
+        }
+        // Once aborted, only the `Abort`s of the other members are still counted.
+        if self.abort_report().is_some() && !matches!(msg, Message::Abort { .. }) {
             return Ok(Vec::new());
         }
         let result = self.process_message(rng, msg.clone());
         match result {
             Ok(mut msgs) => {
                 msgs.extend(self.poll_pending_messages(rng));
+                self.record_sent(&msgs);
+                instrumentation::messages_sent(&msgs);
                 Ok(msgs)
             }
-            Err(Error::UnexpectedPhase { .. }) | Err(Error::MissingPart) => {
-                self.pending_messages.push(msg);
-                Ok(Vec::new())
+            Err(error) => {
+                if self.defer(msg, &error) {
+                    Ok(Vec::new())
+                } else {
+                    Err(error)
+                }
             }
-            Err(_) => result,
         }
     }
 
+    // Buffers `msg` if `error` means it can be handled once we reach a later phase, or once we
+    // got the part it refers to. Returns `false` if the error is a fault of the message instead.
+    // Messages waiting for a phase we already passed, or claiming a sender that isn't a member,
+    // are dropped.
+    fn defer(&mut self, msg: Message, error: &Error) -> bool {
+        let phase = match error {
+            Error::UnexpectedPhase { expected, .. } => *expected,
+            Error::MissingPart => self.phase,
+            // Peers may exclude members and move to the derived context before we do.
+            Error::ContextMismatch { .. } if self.phase == Phase::Complaining => Phase::Commitment,
+            _ => return false,
+        };
+        let kind = msg.kind();
+        let member = matches!(
+            self.node_id_from_index(msg.key_gen_id()),
+            Some(name) if self.names.contains(&name)
+        );
+        if phase < self.phase || !member {
+            self.pending_messages.drop_stale();
+            instrumentation::pending_message_dropped(kind);
+        } else if !self.pending_messages.push(phase, msg) {
+            instrumentation::pending_message_dropped(kind);
+        }
+        true
+    }
+
+    // Handles the buffered messages waiting for the current phase or an earlier one, until that
+    // doesn't take us into another phase.
     fn poll_pending_messages<R: RngCore>(&mut self, rng: &mut R) -> Vec<Message> {
-        let pending_messages = std::mem::take(&mut self.pending_messages);
         let mut msgs = Vec::new();
-        for message in pending_messages {
-            if let Ok(new_messages) = self.process_message(rng, message.clone()) {
-                if self.is_finalized() {
-                    return Vec::new();
+        loop {
+            let phase = self.phase;
+            for message in self.pending_messages.take_ready(phase) {
+                match self.process_message(rng, message.clone()) {
+                    Ok(new_messages) => {
+                        msgs.extend(new_messages);
+                        if self.is_finalized() {
+                            return msgs;
+                        }
+                    }
+                    Err(error) => {
+                        let _ = self.defer(message, &error);
+                    }
                 }
-                msgs.extend(new_messages);
-            } else {
-                self.pending_messages.push(message);
+            }
+            if self.phase == phase {
+                return msgs;
             }
         }
-        msgs
     }
 
     fn process_message<R: RngCore>(
@@ -524,9 +913,338 @@ impl KeyGen {
                 context,
                 ack,
             } => self.handle_ack(key_gen_id, context, ack),
+            Message::Qualification {
+                key_gen_id,
+                context,
+                qual,
+                signature,
+            } => self.handle_qualification(key_gen_id, context, qual, signature),
+            Message::Finalized {
+                key_gen_id,
+                context,
+                public_key_set_digest,
+                signature_share,
+            } => self.handle_finalized(key_gen_id, context, public_key_set_digest, signature_share),
+            Message::ResendRequest {
+                key_gen_id,
+                context,
+                what,
+            } => self.handle_resend_request(key_gen_id, context, what),
+            Message::SyncRequest {
+                key_gen_id,
+                context,
+            } => self.handle_sync_request(key_gen_id, context),
+            Message::SyncResponse {
+                key_gen_id,
+                context,
+                requester,
+                phase,
+                messages,
+                digest,
+                signature,
+            } => self.handle_sync_response(
+                rng, key_gen_id, context, requester, phase, messages, digest, signature,
+            ),
+            Message::Abort {
+                key_gen_id,
+                context,
+                reason,
+                blamed,
+                signature,
+            } => self.handle_abort(key_gen_id, context, reason, blamed, signature),
         }
     }
 
+    // Keeps our outgoing `Proposal`s and `Acknowledgment`s, so that we can send them again.
+    // Messages we already sent, e.g. when answering a `ResendRequest`, are kept once.
+    fn record_sent(&mut self, msgs: &[Message]) {
+        for msg in msgs {
+            let key = match msg {
+                Message::Proposal {
+                    key_gen_id, part, ..
+                } => (MessageKind::Proposal, *key_gen_id, part.receiver),
+                Message::Acknowledgment { ack, .. } => (MessageKind::Acknowledgment, ack.0, ack.1),
+                _ => continue,
+            };
+            let _ = self.sent_messages.entry(key).or_insert_with(|| msg.clone());
+        }
+    }
+
+    /// Returns a `ResendRequest` for each part we miss, and for each part still missing
+    /// acknowledgments addressed to us. These are to be multicast when the round doesn't progress,
+    /// e.g. after messages got lost, and before `timed_phase_transition` gives up on the members.
+    pub fn resend_requests(&self) -> Vec<Message> {
+        if !(self.phase == Phase::Contribution || self.phase == Phase::Commitment) {
+            return Vec::new();
+        }
+        self.context
+            .get_pairs()
+            .iter()
+            .filter_map(|(_, index)| {
+                let what = match self.parts.get(index) {
+                    None => Resend::Proposal(*index),
+                    Some(part) if part.acks.len() < self.names.len() => Resend::Acks(*index),
+                    Some(_) => return None,
+                };
+                Some(Message::ResendRequest {
+                    key_gen_id: self.our_index,
+                    context: self.context.clone(),
+                    what,
+                })
+            })
+            .collect()
+    }
+
+    /// Returns a signed `Abort` giving up on the session for `reason`. It blames our
+    /// [`possible_blockers`](Self::possible_blockers), or in the Complaining phase the members
+    /// failing it, as listed by `Error::TooManyNonVoters`. It is to be multicast to all nodes,
+    /// ourselves included: the session is aborted once a quorum of the members sent one.
+    pub fn abort(&self, reason: AbortReason) -> Result<Message, Error> {
+        let blamed: BTreeSet<u64> = if self.phase == Phase::Complaining {
+            self.complaints_accumulator.finalize_complaining_phase()
+        } else {
+            self.possible_blockers()
+        }
+        .iter()
+        .filter_map(|name| self.node_index(name))
+        .collect();
+        let signature = self.identity.sign(
+            ABORT_LABEL,
+            &self.context,
+            self.our_index,
+            &(reason, &blamed),
+        )?;
+        Ok(Message::Abort {
+            key_gen_id: self.our_index,
+            context: self.context.clone(),
+            reason,
+            blamed,
+            signature,
+        })
+    }
+
+    /// Returns the agreed reason and the blamed members once the `Abort`s of a quorum of members
+    /// blaming the same members aborted the session.
+    ///
+    /// The quorum is the one required by the policy, and at least a majority of the members: two
+    /// members can't agree on different blamed members, whichever `Abort`s each of them received.
+    pub fn abort_report(&self) -> Option<AbortReport> {
+        let quorum = self
+            .policy
+            .abort
+            .required(self.names.len(), self.threshold)
+            .max(Quorum::Majority.required(self.names.len(), self.threshold));
+        let (reason, blamed, aborting) = self.abort_accumulator.agreed(quorum)?;
+        let name = |index: &u64| self.node_id_from_index(*index);
+        Some(AbortReport {
+            reason,
+            blamed: blamed.iter().filter_map(name).collect(),
+            aborting: aborting.iter().filter_map(name).collect(),
+        })
+    }
+
+    // Handles an `Abort` message. Like votes, these are accepted in any phase, once signed by
+    // their sender, and only the first `Abort` of each member is counted.
+    fn handle_abort(
+        &mut self,
+        sender_index: u64,
+        context: ShareXorName,
+        reason: AbortReason,
+        blamed: BTreeSet<u64>,
+        signature: Signature,
+    ) -> Result<Vec<Message>, Error> {
+        self.check_context(&context)?;
+        let sender_id = self
+            .node_id_from_index(sender_index)
+            .ok_or(Error::UnknownSender(sender_index))?;
+        if !self.names.contains(&sender_id) {
+            return Err(Error::UnknownSender(sender_index));
+        }
+        self.identity.verify(
+            ABORT_LABEL,
+            &self.context,
+            sender_index,
+            &(reason, &blamed),
+            &signature,
+        )?;
+        if let Some(unknown) = blamed
+            .iter()
+            .find(|index| self.node_id_from_index(**index).is_none())
+        {
+            return Err(Error::UnknownTarget(*unknown));
+        }
+
+        let aborted = self.abort_report().is_some();
+        self.abort_accumulator
+            .add_vote(sender_index, reason, blamed);
+        if !aborted {
+            if let Some(report) = self.abort_report() {
+                self.finish_round(instrumentation::RESULT_ABORTED);
+                self.events.push(DkgEvent::Aborted(report));
+            }
+        }
+        Ok(Vec::new())
+    }
+
+    /// Returns the `SyncRequest` to multicast when we joined the session late, so that the members
+    /// send us what we need to join the current round.
+    ///
+    /// Syncing only helps members that didn't send their part yet. Our part isn't kept across a
+    /// restart, so after one we propose a new part. Each member complains about the one it
+    /// receives, but can't tell the others apart from those we sent before, so the round completes
+    /// with our first part and without a share for us. We should rather wait for the next session.
+    pub fn sync_request(&self) -> Message {
+        Message::SyncRequest {
+            key_gen_id: self.our_index,
+            context: self.context.clone(),
+        }
+    }
+
+    // Handles a `SyncRequest`, answering with our phase, our `Initialization` and our messages
+    // addressed to the requester. Messages are not signed, so we only send our own ones: the
+    // requester can't be given anything it couldn't have received from us directly.
+    fn handle_sync_request(
+        &mut self,
+        sender_index: u64,
+        context: ShareXorName,
+    ) -> Result<Vec<Message>, Error> {
+        self.check_context(&context)?;
+        let sender_id = self
+            .node_id_from_index(sender_index)
+            .ok_or(Error::UnknownSender(sender_index))?;
+        if !self.names.contains(&sender_id) || sender_index == self.our_index {
+            return Ok(Vec::new());
+        }
+
+        let answered = self.syncs.entry(sender_index).or_insert(0);
+        if *answered >= MAX_RESENDS {
+            debug!(
+                "{:?} ignores SyncRequest of {} over the limit",
+                self, sender_index
+            );
+            return Ok(Vec::new());
+        }
+        *answered += 1;
+
+        let messages: Vec<Message> = self
+            .sent_messages
+            .iter()
+            .filter(|((kind, _, receiver), _)| {
+                *kind == MessageKind::Initialization || *receiver == sender_index
+            })
+            .map(|(_, msg)| msg.clone())
+            .collect();
+        let digest = sync_digest(&self.context, sender_index, self.phase, &messages)?;
+        let signature =
+            self.identity
+                .sign(SYNC_RESPONSE_LABEL, &self.context, self.our_index, &digest)?;
+        Ok(vec![Message::SyncResponse {
+            key_gen_id: self.our_index,
+            context: self.context.clone(),
+            requester: sender_index,
+            phase: self.phase,
+            messages,
+            digest,
+            signature,
+        }])
+    }
+
+    // Handles a `SyncResponse` addressed to us and signed by its sender, handling the messages of
+    // the sender it carries as if they had just arrived.
+    #[allow(clippy::too_many_arguments)]
+    fn handle_sync_response<R: RngCore>(
+        &mut self,
+        rng: &mut R,
+        sender_index: u64,
+        context: ShareXorName,
+        requester: u64,
+        phase: Phase,
+        messages: Vec<Message>,
+        digest: [u8; 32],
+        signature: Signature,
+    ) -> Result<Vec<Message>, Error> {
+        self.check_context(&context)?;
+        if requester != self.our_index {
+            return Ok(Vec::new());
+        }
+        let sender_id = self
+            .node_id_from_index(sender_index)
+            .ok_or(Error::UnknownSender(sender_index))?;
+        if !self.names.contains(&sender_id) {
+            return Err(Error::UnknownSender(sender_index));
+        }
+        if sync_digest(&self.context, requester, phase, &messages)? != digest {
+            return Err(Error::SyncDigestMismatch(sender_index));
+        }
+        self.identity.verify(
+            SYNC_RESPONSE_LABEL,
+            &self.context,
+            sender_index,
+            &digest,
+            &signature,
+        )?;
+        debug!(
+            "{:?} syncs with {} in phase {:?}",
+            self, sender_index, phase
+        );
+
+        let mut result = Vec::new();
+        for msg in messages {
+            let own = matches!(
+                msg,
+                Message::Initialization { .. }
+                    | Message::Proposal { .. }
+                    | Message::Acknowledgment { .. }
+            ) && msg.key_gen_id() == sender_index;
+            if !own {
+                continue;
+            }
+            match self.process_message(rng, msg.clone()) {
+                Ok(msgs) => result.extend(msgs),
+                Err(error) => {
+                    if !self.defer(msg, &error) {
+                        debug!("{:?} dropped a synced message: {:?}", self, error);
+                    }
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    // Handles a `ResendRequest`, answering with our messages addressed to the requester. We answer
+    // at most `MAX_RESENDS` requests per member and part during a round, whatever they ask for.
+    fn handle_resend_request(
+        &mut self,
+        sender_index: u64,
+        context: ShareXorName,
+        what: Resend,
+    ) -> Result<Vec<Message>, Error> {
+        self.check_context(&context)?;
+        let sender_id = self
+            .node_id_from_index(sender_index)
+            .ok_or(Error::UnknownSender(sender_index))?;
+        if !self.names.contains(&sender_id) || sender_index == self.our_index {
+            return Ok(Vec::new());
+        }
+
+        let limit = MAX_RESENDS * self.names.len();
+        let answered = self.resends.entry(sender_index).or_insert(0);
+        if *answered >= limit {
+            debug!(
+                "{:?} ignores {:?} of {} over the limit",
+                self, what, sender_index
+            );
+            return Ok(Vec::new());
+        }
+        *answered += 1;
+
+        let key = match what {
+            Resend::Proposal(proposer) => (MessageKind::Proposal, proposer, sender_index),
+            Resend::Acks(proposer) => (MessageKind::Acknowledgment, proposer, sender_index),
+        };
+        Ok(self.sent_messages.get(&key).cloned().into_iter().collect())
+    }
+
     // Handles an incoming initialize message. Creates the `Proposal` message once quorumn
     // agreement reached, and the message should be multicast to all nodes.
     fn handle_initialization<R: RngCore>(
@@ -539,102 +1257,97 @@ impl KeyGen {
         member_list: BTreeSet<XorName>,
         mode: Mode, //sharezero: bool,
     ) -> Result<Vec<Message>, Error> {
-        if self.context != context {
-            return Err(Error::ContextMismatch {
-                expected: self.context.clone(),
-                actual: context,
-            });
-        }
         if self.phase != Phase::Initialization {
+            self.check_context(&context)?;
             return Err(Error::UnexpectedPhase {
                 expected: Phase::Initialization,
                 actual: self.phase,
             });
         }
 
-        if let Some((m, _n, member_list)) =
-            self.initalization_accumulator
-                .add_initialization(m, n, sender, member_list)
-        {
-            self.threshold = m;
-            self.names = member_list;
-            self.phase = Phase::Contribution;
-
-            let mut rng = rng_adapter::RngAdapter(&mut *rng);
-            let our_part = match mode {
-                // If this in an initial keygen, we generate a new random bivariate polynomial,
-                // including a new constant term.
-                Mode::Initial => BivarPoly::random(self.threshold, &mut rng),
-                // If it is a refresh, we generate a new random bivariate polynomial, but with
-                // zero constant term.
-                Mode::Refresh => BivarPoly::random_zeroconstant(self.threshold, &mut rng),
-                // If it is a recovery,
-                Mode::Recovery(shareindex) => {
-                    let r: Fr = (shareindex + 1).into_fr();
-                    BivarPoly::random_zero_at(self.threshold, r, &mut rng)
-                }
-            };
+        let params = InitializationParams {
+            m,
+            n,
+            member_list,
+            context,
+            mode,
+        };
+        let context_check = self.check_context(&params.context);
+        let agreed = self
+            .initalization_accumulator
+            .add_initialization(sender, params);
+        context_check?;
+
+        if let Some(params) = agreed {
+            self.threshold = params.m;
+            self.names = params.member_list;
+            self.mode = params.mode;
+            self.set_phase(Phase::Contribution);
+
+            let our_part = new_bivar_poly(rng, self.threshold, &self.mode);
+            return self.create_proposals(&our_part);
+        }
+        Ok(Vec::new())
+    }
 
-            let ack = our_part.commitment();
-            let encrypt = |(i, name): (usize, &XorName)| {
-                let row = our_part.row(i + 1);
-                self.encryptor.encrypt(name, &serialize(&row)?)
-            };
-            let rows = self
-                .context
-                .get_pairs()
-                .iter()
-                .map(|(name, i)| encrypt((*i as usize, name)))
-                .collect::<Result<Vec<_>, Error>>()?;
-            // let rows = self
-            //     .names
-            //     .iter()
-            //     .enumerate()
-            //     .map(encrypt)
-            //     .collect::<Result<Vec<_>, Error>>()?;
-
-            let result = self
-                .context
-                .get_pairs()
-                .iter()
-                .map(|(_pk, idx)| {
-                    let ser_row = serialize(&our_part.row(idx + 1))?;
-                    Ok(Message::Proposal {
-                        key_gen_id: self.our_index,
+    /// Returns the indices of the peers whose `Initialization` proposed other parameters than
+    /// the agreed ones, or than ours while none got agreed yet. That covers the threshold, the
+    /// members, the context and the mode.
+    pub fn initialization_conflicts(&self) -> BTreeSet<u64> {
+        let expected = match &self.initalization_accumulator.agreed {
+            Some(agreed) => agreed.clone(),
+            None => InitializationParams {
+                m: self.threshold,
+                n: self.names.len(),
+                member_list: self.names.clone(),
+                context: self.context.clone(),
+                mode: self.mode.clone(),
+            },
+        };
+        self.initalization_accumulator.conflicts(&expected)
+    }
+
+    // Creates the `Proposal` messages carrying our part, one for each node, that should be
+    // multicast to all nodes.
+    fn create_proposals(&self, our_part: &BivarPoly) -> Result<Vec<Message>, Error> {
+        let commitment = our_part.commitment();
+        let encrypt = |(i, name): (usize, &XorName)| {
+            let row = our_part.row(i + 1);
+            self.encryptor.encrypt(name, &serialize(&row)?)
+        };
+        let rows = self
+            .context
+            .get_pairs()
+            .iter()
+            .map(|(name, i)| encrypt((*i as usize, name)))
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        // Prove that we know the constant term we committed to, so that it cannot have been
+        // chosen as a function of the other proposers' commitments. In a refresh, the constant
+        // term is zero and the proof is not checked.
+        let pop_msg = proof_of_possession_msg(&self.context, self.our_index, &commitment)?;
+        let mut constant = our_part.evaluate(0, 0);
+        let proof = SecretKey::from_mut(&mut constant).sign(pop_msg);
+
+        self.context
+            .get_pairs()
+            .iter()
+            .map(|(_pk, idx)| {
+                let ser_row = serialize(&our_part.row(idx + 1))?;
+                Ok(Message::Proposal {
+                    key_gen_id: self.our_index,
+                    context: self.context.clone(),
+                    part: Part {
+                        receiver: *idx as u64,
                         context: self.context.clone(),
-                        part: Part {
-                            receiver: *idx as u64,
-                            context: self.context.clone(),
-                            commitment: ack.clone(),
-                            ser_row,
-                            enc_rows: rows.clone(),
-                        },
-                    })
+                        commitment: commitment.clone(),
+                        ser_row,
+                        enc_rows: rows.clone(),
+                        proof: proof.clone(),
+                    },
                 })
-                .collect::<Result<Vec<_>, Error>>()?;
-
-            // let result = self
-            //     .names
-            //     .iter()
-            //     .enumerate()
-            //     .map(|(idx, _pk)| {
-            //         let ser_row = serialize(&our_part.row(idx + 1))?;
-            //         Ok(Message::Proposal {
-            //             key_gen_id: self.our_index,
-            //             context: context,
-            //             part: Part {
-            //                 receiver: idx as u64,
-            //                 context: context,
-            //                 commitment: ack.clone(),
-            //                 ser_row,
-            //                 enc_rows: rows.clone(),
-            //             },
-            //         })
-            //     })
-            //     .collect::<Result<Vec<_>, Error>>()?;
-            return Ok(result);
-        }
-        Ok(Vec::new())
+            })
+            .collect()
     }
 
     // Handles a `Proposal` message during the `Contribution` phase.
@@ -646,23 +1359,15 @@ impl KeyGen {
         context: ShareXorName,
         part: Part,
     ) -> Result<Vec<Message>, Error> {
-        if self.context != context {
-            return Err(Error::ContextMismatch {
-                expected: self.context.clone(),
-                actual: context,
-            });
-        }
+        self.check_context(&context)?;
         if !(self.phase == Phase::Contribution || self.phase == Phase::Commitment) {
-            return Err(Error::UnexpectedPhase {
-                expected: Phase::Contribution,
-                actual: self.phase,
-            });
+            return Err(self.not_contributing());
         }
 
         let row = match self.handle_part_or_fault(sender_index, part.clone()) {
             Ok(Some(row)) => row,
             Ok(None) => return Ok(Vec::new()),
-            Err(_fault) => {
+            Err(fault) => {
                 let msg = Message::Proposal {
                     key_gen_id: sender_index,
                     context,
@@ -670,20 +1375,24 @@ impl KeyGen {
                 };
                 debug!(
                     "{:?} complain {:?} with Error {:?}",
-                    self, sender_index, _fault
+                    self, sender_index, fault
                 );
                 let invalid_contribute = serialize(&msg)?;
-                self.pending_complain_messages.push(Message::Complaint {
-                    key_gen_id: self.our_index,
-                    target: sender_index,
-                    context: self.context.clone(),
-                    msg: invalid_contribute,
-                });
+                self.raise_complaint(
+                    sender_index,
+                    ComplaintReason::Part(fault),
+                    invalid_contribute,
+                );
                 return Ok(Vec::new());
             }
         };
 
-        // The row is valid. Encrypt one value for each node and broadcast `Acknowledgment`.
+        self.acknowledge_row(sender_index, &row)
+    }
+
+    // Encrypts one value of the verified `row` proposed by `proposer` for each node, returning
+    // the `Acknowledgment`s to broadcast.
+    fn acknowledge_row(&self, proposer_index: u64, row: &Poly) -> Result<Vec<Message>, Error> {
         let mut values = BTreeMap::new(); //Vec::new();
         let mut enc_values = BTreeMap::new();
         for (pk, index) in self.context.get_pairs() {
@@ -708,7 +1417,7 @@ impl KeyGen {
                 key_gen_id: self.our_index,
                 context: self.context.clone(),
                 ack: Acknowledgment(
-                    sender_index,
+                    proposer_index,
                     *idx as u64,
                     values[idx].clone(),
                     enc_values.values().cloned().collect::<Vec<Vec<u8>>>(), //fix
@@ -727,27 +1436,26 @@ impl KeyGen {
         context: ShareXorName,
         ack: Acknowledgment,
     ) -> Result<Vec<Message>, Error> {
-        if self.context != context {
-            return Err(Error::ContextMismatch {
-                expected: self.context.clone(),
-                actual: context,
-            });
-        }
+        self.check_context(&context)?;
         if !(self.phase == Phase::Contribution || self.phase == Phase::Commitment) {
-            return Err(Error::UnexpectedPhase {
-                expected: Phase::Contribution,
-                actual: self.phase,
-            });
+            return Err(self.not_contributing());
         }
+        let proposer_index = ack.0;
         match self.handle_ack_or_fault(sender_index, ack.clone()) {
-            Ok(()) => {
+            Ok(recovered_row) => {
+                // Having rebuilt our row, we acknowledge it as if we had received our `Part`.
+                let mut result = match recovered_row {
+                    Some(row) => self.acknowledge_row(proposer_index, &row)?,
+                    None => Vec::new(),
+                };
                 if self.all_contribution_received() {
                     if self.phase == Phase::Commitment {
-                        self.become_finalization();
+                        result.extend(self.become_finalization()?);
                     } else {
-                        return self.finalize_contributing_phase();
+                        result.extend(self.finalize_contributing_phase()?);
                     }
                 }
+                return Ok(result);
             }
             Err(AcknowledgmentFault::MissingPart) => {
                 debug!(
@@ -767,19 +1475,31 @@ impl KeyGen {
                     "{:?} complain {:?} with Error {:?}",
                     self, sender_index, fault
                 );
-
                 let invalid_ack = serialize(&msg)?;
-                self.pending_complain_messages.push(Message::Complaint {
-                    key_gen_id: self.our_index,
-                    target: sender_index,
-                    context,
-                    msg: invalid_ack,
-                });
+                self.raise_complaint(
+                    sender_index,
+                    ComplaintReason::Acknowledgment(fault),
+                    invalid_ack,
+                );
             }
         }
         Ok(Vec::new())
     }
 
+    // Returns the error for a `Proposal` or an `Acknowledgment` arriving outside of the phases
+    // handling them, expecting the next such phase.
+    fn not_contributing(&self) -> Error {
+        let expected = if self.phase < Phase::Contribution {
+            Phase::Contribution
+        } else {
+            Phase::Commitment
+        };
+        Error::UnexpectedPhase {
+            expected,
+            actual: self.phase,
+        }
+    }
+
     pub fn all_contribution_received(&self) -> bool {
         self.names.len() == self.parts.len()
             && self
@@ -789,19 +1509,18 @@ impl KeyGen {
     }
 
     fn finalize_contributing_phase(&mut self) -> Result<Vec<Message>, Error> {
-        self.phase = Phase::Complaining;
+        self.set_phase(Phase::Complaining);
 
         for non_contributor in self.non_contributors().0 {
             debug!(
                 "{:?} complain {:?} for non-contribution during Contribution phase",
                 self, non_contributor
             );
-            self.pending_complain_messages.push(Message::Complaint {
-                key_gen_id: self.our_index,
-                target: non_contributor,
-                context: self.context.clone(),
-                msg: b"Not contributed".to_vec(),
-            });
+            self.raise_complaint(
+                non_contributor,
+                ComplaintReason::NonContribution,
+                b"Not contributed".to_vec(),
+            );
         }
         debug!(
             "{:?} has {:?} complain message and is {:?} ready ({:?} - {:?})",
@@ -813,13 +1532,30 @@ impl KeyGen {
         );
         // In case of ready, transit into `Finalization` phase.
         if self.is_ready() {
-            self.become_finalization();
+            return self.become_finalization();
         }
 
-        self.pending_messages.clear();
+        // Complaints of the members that got here before us stay buffered.
+        self.pending_messages.drop_before(self.phase);
         Ok(mem::take(&mut self.pending_complain_messages))
     }
 
+    // Queues a `Complaint` against `target`, to be multicast once the contribution phase ends.
+    fn raise_complaint(&mut self, target: u64, reason: ComplaintReason, msg: Vec<u8>) {
+        instrumentation::complaint_raised(&reason);
+        if let Some(target_id) = self.node_id_from_index(target) {
+            let _ = self.complaint_reasons.insert(target_id, reason);
+        }
+        self.events
+            .push(DkgEvent::ComplaintRaised { target, reason });
+        self.pending_complain_messages.push(Message::Complaint {
+            key_gen_id: self.our_index,
+            target,
+            context: self.context.clone(),
+            msg,
+        });
+    }
+
     fn non_contributors(&self) -> (BTreeSet<u64>, BTreeSet<XorName>) {
         let mut non_idxes = BTreeSet::new();
         let mut non_ids = BTreeSet::new();
@@ -830,7 +1566,11 @@ impl KeyGen {
                 if !proposal_sate.acks.contains(&(*idx as u64)) {
                     let times = missing_times.entry(idx).or_insert_with(|| 0);
                     *times += 1;
-                    if *times > self.names.len() / 2 {
+                    let required = self
+                        .policy
+                        .non_contribution
+                        .required(self.names.len(), self.threshold);
+                    if *times >= required {
                         let _ = non_idxes.insert(*idx as u64);
                         let _ = non_ids.insert(*name);
                     }
@@ -852,7 +1592,7 @@ impl KeyGen {
         rng: &mut R,
     ) -> Result<Vec<Message>, Error> {
         debug!("{:?} current phase is {:?}", self, self.phase);
-        match self.phase {
+        let result = match self.phase {
             Phase::Contribution => match self.finalize_contributing_phase() {
                 Ok(mut messages) => {
                     messages.extend(self.poll_pending_messages(rng));
@@ -877,7 +1617,12 @@ impl KeyGen {
             }),
 
             Phase::Finalization => Ok(Vec::new()),
+        };
+        if let Ok(msgs) = &result {
+            self.record_sent(msgs);
+            instrumentation::messages_sent(msgs);
         }
+        result
     }
 
     // Handles a `Complaint` message.
@@ -888,12 +1633,7 @@ impl KeyGen {
         context: ShareXorName,
         invalid_msg: Vec<u8>,
     ) -> Result<Vec<Message>, Error> {
-        if self.context != context {
-            return Err(Error::ContextMismatch {
-                expected: self.context.clone(),
-                actual: context,
-            });
-        }
+        self.check_context(&context)?;
 
         if self.phase != Phase::Complaining {
             return Err(Error::UnexpectedPhase {
@@ -904,10 +1644,10 @@ impl KeyGen {
 
         let sender_id = self
             .node_id_from_index(sender_index)
-            .ok_or(Error::UnknownSender)?;
+            .ok_or(Error::UnknownSender(sender_index))?;
         let target_id = self
             .node_id_from_index(target_index)
-            .ok_or(Error::Unknown)?;
+            .ok_or(Error::UnknownTarget(target_index))?;
 
         self.complaints_accumulator
             .add_complaint(sender_id, target_id, invalid_msg);
@@ -926,6 +1666,7 @@ impl KeyGen {
                     let _ = result.insert(index);
                 }
             });
+            self.finish_round(instrumentation::RESULT_TOO_MANY_NON_VOTERS);
             return Err(Error::TooManyNonVoters(result));
         }
 
@@ -949,54 +1690,38 @@ impl KeyGen {
         if !failings.is_empty() {
             for failing in failings.iter() {
                 let _ = self.names.remove(failing);
+                instrumentation::member_excluded(self.complaint_reasons.get(failing));
+                self.events.push(DkgEvent::MemberExcluded(*failing));
             }
-            self.our_index = self.node_index(&self.our_id).ok_or(Error::Unknown)?;
+            if !self.names.contains(&self.our_id) {
+                self.finish_round(instrumentation::RESULT_EVICTED);
+                return Err(Error::EvictedSelf);
+            }
+            // The new round runs in a context derived from the excluded members, so that messages
+            // of the previous round are rejected and the remaining members keep their shares.
+            self.context = self.context.excluding(&failings);
+            self.our_index = self
+                .node_index(&self.our_id)
+                .ok_or(Error::NotAMember(self.our_id))?;
+            self.complaints_accumulator =
+                ComplaintsAccumulator::new(self.names.clone(), self.threshold, self.policy);
         } else if self.is_ready() {
-            self.become_finalization();
-            return Ok(Vec::new());
+            return self.become_finalization();
         }
 
-        self.phase = Phase::Commitment;
+        self.set_phase(Phase::Commitment);
         self.parts = BTreeMap::new();
-
-        let mut rng = rng_adapter::RngAdapter(&mut *rng);
-        let our_part = BivarPoly::random(self.threshold, &mut rng);
-        let justify = our_part.commitment();
-        let encrypt = |(i, name): (usize, &XorName)| {
-            let row = our_part.row(i + 1);
-            self.encryptor.encrypt(name, &serialize(&row)?)
-        };
-
-        let rows = self
-            .context
-            .get_pairs()
-            .iter()
-            .map(|(name, i)| encrypt((*i as usize, name)))
-            .collect::<Result<Vec<_>, Error>>()?;
-
-        // let rows = self
-        //     .names
-        //     .iter()
-        //     .enumerate()
-        //     .map(encrypt)
-        //     .collect::<Result<Vec<_>, Error>>()?;
-
-        //        self.names.iter().enumerate().for_each(|(idx, _pk)| {
-        self.context.get_pairs().iter().for_each(|(_pk, idx)| {
-            if let Ok(ser_row) = serialize(&our_part.row(idx + 1)) {
-                result.push(Message::Proposal {
-                    key_gen_id: self.our_index,
-                    context: self.context.clone(),
-                    part: Part {
-                        receiver: *idx as u64,
-                        context: self.context.clone(),
-                        commitment: justify.clone(),
-                        ser_row,
-                        enc_rows: rows.clone(),
-                    },
-                });
-            }
-        });
+        self.row_recoveries.clear();
+        self.sent_messages.clear();
+        self.resends.clear();
+        self.syncs.clear();
+        self.round_finished = false;
+        self.abort_accumulator = AbortAccumulator::default();
+        self.complaint_reasons.clear();
+
+        // The new round must keep the relationship with the previous keys that our mode requires.
+        let our_part = new_bivar_poly(rng, self.threshold, &self.mode);
+        result.extend(self.create_proposals(&our_part)?);
 
         Ok(result)
     }
@@ -1009,19 +1734,166 @@ impl KeyGen {
         _keys_map: BTreeMap<XorName, (Key, Iv)>,
     ) -> Result<Vec<Message>, Error> {
         // TODO: Need to decide how the justification and recover procedure take out.
-        if self.context != context {
-            return Err(Error::ContextMismatch {
-                expected: self.context.clone(),
-                actual: context,
-            });
+        self.check_context(&context)?;
+        Ok(Vec::new())
+    }
+
+    // Handles a `Qualification` vote. Votes are accepted in any phase, as peers may finalize
+    // before we do, but only counted once signed by their sender.
+    fn handle_qualification(
+        &mut self,
+        sender_index: u64,
+        context: ShareXorName,
+        qual: BTreeSet<u64>,
+        signature: Signature,
+    ) -> Result<Vec<Message>, Error> {
+        self.check_context(&context)?;
+        let sender_id = self
+            .node_id_from_index(sender_index)
+            .ok_or(Error::UnknownSender(sender_index))?;
+        if !self.names.contains(&sender_id) {
+            return Err(Error::UnknownSender(sender_index));
+        }
+        self.identity.verify(
+            QUALIFICATION_LABEL,
+            &self.context,
+            sender_index,
+            &qual,
+            &signature,
+        )?;
+
+        self.qualification_accumulator.add_vote(sender_index, qual);
+        self.confirm()
+    }
+
+    // Handles a `Finalized` message. Like votes, these are accepted in any phase.
+    fn handle_finalized(
+        &mut self,
+        sender_index: u64,
+        context: ShareXorName,
+        public_key_set_digest: [u8; 32],
+        signature_share: SignatureShare,
+    ) -> Result<Vec<Message>, Error> {
+        self.check_context(&context)?;
+        let sender_id = self
+            .node_id_from_index(sender_index)
+            .ok_or(Error::UnknownSender(sender_index))?;
+        if !self.names.contains(&sender_id) {
+            return Err(Error::UnknownSender(sender_index));
+        }
+
+        self.confirmation_accumulator.add_share(
+            sender_index,
+            public_key_set_digest,
+            signature_share,
+        );
+        if let Ok((_, outcome)) = self.generate_keys() {
+            let digest = public_key_set_digest_of(&outcome.public_key_set)?;
+            self.confirmation_accumulator
+                .try_combine(&outcome.public_key_set, digest);
         }
         Ok(Vec::new())
     }
 
-    fn become_finalization(&mut self) {
-        self.phase = Phase::Finalization;
+    // Once the qualified set is agreed, signs the new group public key with our new secret-key
+    // share and returns the `Finalized` message that shall be multicast to all nodes.
+    fn confirm(&mut self) -> Result<Vec<Message>, Error> {
+        if self.confirmation_accumulator.sent || self.agreed_qual().is_none() {
+            return Ok(Vec::new());
+        }
+        let outcome = match self.generate_keys() {
+            Ok((_, outcome)) => outcome,
+            Err(err) => {
+                debug!("{:?} cannot confirm the generated keys: {:?}", self, err);
+                return Ok(Vec::new());
+            }
+        };
+
+        self.finish_round(instrumentation::RESULT_FINALIZED);
+        self.events.push(DkgEvent::Finalized {
+            pk: outcome.public_key_set.public_key(),
+        });
+        let digest = public_key_set_digest_of(&outcome.public_key_set)?;
+        let signature_share = outcome
+            .secret_key_share
+            .sign(outcome.public_key_set.public_key().to_bytes());
+        self.confirmation_accumulator.sent = true;
+        self.confirmation_accumulator
+            .add_share(self.our_index, digest, signature_share.clone());
+        self.confirmation_accumulator
+            .try_combine(&outcome.public_key_set, digest);
+
+        Ok(vec![Message::Finalized {
+            key_gen_id: self.our_index,
+            context: self.context.clone(),
+            public_key_set_digest: digest,
+            signature_share,
+        }])
+    }
+
+    // Transits into the `Finalization` phase, returning our signed `Qualification` vote that
+    // shall be multicast to all nodes, together with our `Finalized` message if that vote
+    // completed the agreement.
+    fn become_finalization(&mut self) -> Result<Vec<Message>, Error> {
+        let qual = self.complete_parts();
+        let signature =
+            self.identity
+                .sign(QUALIFICATION_LABEL, &self.context, self.our_index, &qual)?;
+
+        self.set_phase(Phase::Finalization);
         self.pending_messages.clear();
         self.pending_complain_messages.clear();
+
+        self.qualification_accumulator
+            .add_vote(self.our_index, qual.clone());
+        let mut result = vec![Message::Qualification {
+            key_gen_id: self.our_index,
+            context: self.context.clone(),
+            qual,
+            signature,
+        }];
+        match self.confirm() {
+            Ok(msgs) => result.extend(msgs),
+            Err(err) => debug!("{:?} failed to confirm the generated keys: {:?}", self, err),
+        }
+        Ok(result)
+    }
+
+    // Records how the current round finished, once, as a failed phase transition may be retried
+    // and an abort may follow it.
+    fn finish_round(&mut self, result: &'static str) {
+        if !self.round_finished {
+            self.round_finished = true;
+            instrumentation::round_finished(&self.mode, result);
+        }
+    }
+
+    fn set_phase(&mut self, phase: Phase) {
+        if self.phase != phase {
+            instrumentation::phase_finished(self.phase, self.phase_started.elapsed());
+            self.phase_started = Instant::now();
+            self.events.push(DkgEvent::PhaseChanged {
+                from: self.phase,
+                to: phase,
+            });
+            self.phase = phase;
+        }
+    }
+
+    /// Returns the events queued since the last call, oldest first.
+    pub fn drain_events(&mut self) -> Vec<DkgEvent> {
+        mem::take(&mut self.events)
+    }
+
+    // Rejects messages belonging to another DKG session or round.
+    fn check_context(&self, context: &ShareXorName) -> Result<(), Error> {
+        if self.context != *context {
+            return Err(Error::ContextMismatch {
+                expected: Box::new(self.context.clone()),
+                actual: Box::new(context.clone()),
+            });
+        }
+        Ok(())
     }
 
     /// Returns the index of the node, or `None` if it is unknown.
@@ -1053,6 +1925,27 @@ impl KeyGen {
             .count()
     }
 
+    /// Returns the indices of the proposers whose parts are complete.
+    fn complete_parts(&self) -> BTreeSet<u64> {
+        self.parts
+            .iter()
+            .filter(|(_, part)| part.is_complete(self.threshold))
+            .map(|(index, _)| *index)
+            .collect()
+    }
+
+    /// Returns the number of matching `Qualification` votes required to agree on the qualified
+    /// set: more than the threshold, and a majority so that no two sets can both be agreed.
+    fn qualification_quorum(&self) -> usize {
+        std::cmp::max(self.threshold + 1, self.names.len() / 2 + 1)
+    }
+
+    /// Returns the qualified set of proposers agreed by a quorum, if any.
+    pub fn agreed_qual(&self) -> Option<&BTreeSet<u64>> {
+        self.qualification_accumulator
+            .agreed(self.qualification_quorum())
+    }
+
     // Returns `true` if all parts are complete to safely generate the new key.
     fn is_ready(&self) -> bool {
         self.complete_parts_count() == self.names.len()
@@ -1068,26 +1961,49 @@ impl KeyGen {
     }
 
     /// Returns the new secret key share and the public key set.
-    pub fn generate_keys(&self) -> Option<(BTreeSet<XorName>, Outcome)> {
+    ///
+    /// Only the parts of the agreed qualified set are summed up, so that all honest nodes derive
+    /// the same `PublicKeySet`. Returns `Error::QualMismatch` if we don't hold a complete part for
+    /// every member of that set.
+    pub fn generate_keys(&self) -> Result<(BTreeSet<XorName>, Outcome), Error> {
         if !self.is_finalized() {
-            return None;
+            return Err(Error::UnexpectedPhase {
+                expected: Phase::Finalization,
+                actual: self.phase,
+            });
+        }
+
+        let qual = self.agreed_qual().ok_or(Error::QualNotAgreed)?;
+        let local = self.complete_parts();
+        if !qual.is_subset(&local) {
+            return Err(Error::QualMismatch {
+                agreed: qual.clone(),
+                local,
+            });
         }
 
         let mut pk_commitment = Poly::zero().commitment();
         let mut sk_val = Fr::zero();
-        let is_complete = |part: &&ProposalState| part.is_complete(self.threshold);
-        for part in self.parts.values().filter(is_complete) {
+        for part in qual.iter().filter_map(|index| self.parts.get(index)) {
             pk_commitment += part.commitment.row(0);
             let row = Poly::interpolate(part.values.iter().take(self.threshold + 1));
             sk_val.add_assign(&row.evaluate(0));
         }
         let sk = SecretKeyShare::from_mut(&mut sk_val);
-        Some((
+        Ok((
             self.names.clone(),
             Outcome::new(pk_commitment.into(), sk, self.our_index as usize),
         ))
     }
 
+    /// Returns our outcome together with the group signature over the new public key, once more
+    /// than threshold members confirmed deriving the same `PublicKeySet`.
+    pub fn confirmed_outcome(&self) -> Option<ConfirmedOutcome> {
+        let signature = self.confirmation_accumulator.signature.clone()?;
+        let (_, outcome) = self.generate_keys().ok()?;
+        Some(ConfirmedOutcome::new(outcome, signature))
+    }
+
     /// This function shall be called when the DKG procedure not reach Finalization phase and before
     /// discarding the instace. It returns potential invalid peers that causing the blocking, if
     /// any and provable.
@@ -1131,6 +2047,34 @@ impl KeyGen {
         result
     }
 
+    /// Returns a snapshot of our progress, to diagnose a stalled session.
+    pub fn progress(&self) -> DkgProgress {
+        let mut pending_messages = BTreeMap::new();
+        for msg in self.pending_messages.iter() {
+            *pending_messages.entry(msg.kind()).or_insert(0) += 1;
+        }
+        DkgProgress {
+            phase: self.phase,
+            threshold: self.threshold,
+            parts_held: self.parts.keys().cloned().collect(),
+            ack_counts: self
+                .parts
+                .iter()
+                .map(|(index, part)| (*index, part.acks.len()))
+                .collect(),
+            complete_parts: self.complete_parts_count(),
+            pending_messages,
+            dropped_messages: self.pending_messages.dropped(),
+            complaints: self
+                .complaints_accumulator
+                .complaints
+                .iter()
+                .map(|(target, accusers)| (*target, accusers.len()))
+                .collect(),
+            blockers: self.possible_blockers(),
+        }
+    }
+
     /// Handles a `Part`, returns a `PartFault` if it is invalid.
     fn handle_part_or_fault(
         &mut self,
@@ -1141,6 +2085,7 @@ impl KeyGen {
             commitment,
             ser_row,
             enc_rows,
+            proof,
         }: Part,
     ) -> Result<Option<Poly>, PartFault> {
         // if self.context != context {
@@ -1154,6 +2099,7 @@ impl KeyGen {
             return Err(PartFault::RowCount);
         }
         if receiver != self.our_index {
+            self.overhear_part(sender_index, commitment, &proof);
             return Ok(None);
         }
         if let Some(state) = self.parts.get(&sender_index) {
@@ -1162,38 +2108,153 @@ impl KeyGen {
             }
             return Ok(None); // We already handled this `Part` before.
         }
+        if !self.fits_mode(&commitment) {
+            return Err(PartFault::Mode);
+        }
+        if !self.has_proof_of_possession(sender_index, &commitment, &proof) {
+            return Err(PartFault::ProofOfPossession);
+        }
         let ack_row = commitment.row(self.our_index + 1);
-        // Retrieve our own row's commitment, and store the full commitment.
-        let _ = self
-            .parts
-            .insert(sender_index, ProposalState::new(commitment));
+        // Retrieve our own row's commitment, and store the full commitment, keeping the values
+        // already acknowledged to us if we started recovering the row.
+        let state = match self.row_recoveries.remove(&sender_index) {
+            Some(recovery) if recovery.commitment == commitment => recovery.into_proposal_state(),
+            _ => ProposalState::new(commitment),
+        };
+        let _ = self.parts.insert(sender_index, state);
 
         let row: Poly = deserialize(&ser_row).map_err(|_| PartFault::DeserializeRow)?;
         if row.commitment() != ack_row {
             return Err(PartFault::RowAcknowledgment);
         }
+        self.events
+            .push(DkgEvent::PartAccepted { from: sender_index });
         Ok(Some(row))
     }
 
-    /// Handles an acknowledgment.
+    // Keeps the commitment of a `Part` addressed to another member, in case we miss ours.
+    fn overhear_part(&mut self, proposer: u64, commitment: BivarCommitment, proof: &Signature) {
+        if self.parts.contains_key(&proposer)
+            || self.row_recoveries.contains_key(&proposer)
+            || !self.fits_mode(&commitment)
+            || !self.has_proof_of_possession(proposer, &commitment, proof)
+        {
+            return;
+        }
+        let _ = self.row_recoveries.insert(
+            proposer,
+            RowRecovery {
+                commitment,
+                values: BTreeMap::new(),
+            },
+        );
+    }
+
+    // Returns whether the commitment is zero where the polynomials of our mode must be, see
+    // `new_bivar_poly`.
+    fn fits_mode(&self, commitment: &BivarCommitment) -> bool {
+        let zero = G1Affine::one().mul(0u64.into_fr());
+        match self.mode {
+            Mode::Initial => true,
+            Mode::Refresh => commitment.evaluate(0u64, 0) == zero,
+            Mode::Recovery(shareindex) => commitment.evaluate(shareindex + 1, 0) == zero,
+        }
+    }
+
+    fn has_proof_of_possession(
+        &self,
+        proposer: u64,
+        commitment: &BivarCommitment,
+        proof: &Signature,
+    ) -> bool {
+        // The constant term of a refresh is zero, so there is nothing to prove: any proof verifies
+        // against the zero key. `fits_mode` checks the constant term instead.
+        if self.mode == Mode::Refresh {
+            return true;
+        }
+        match proof_of_possession_msg(&self.context, proposer, commitment) {
+            Ok(pop_msg) => PublicKeySet::from(commitment.row(0))
+                .public_key()
+                .verify(proof, pop_msg),
+            Err(_) => false,
+        }
+    }
+
+    // Adds a value acknowledged to us for a `Part` we missed. Once more than threshold values
+    // verify against the overheard commitment, rebuilds our row from them and returns it.
+    //
+    // Values that do not verify are dropped rather than complained about, as the commitment only
+    // comes from a `Part` addressed to someone else.
+    fn recover_row(
+        &mut self,
+        proposer_index: u64,
+        sender_index: u64,
+        ser_val: &[u8],
+    ) -> Result<Option<Poly>, AcknowledgmentFault> {
+        let our_index = self.our_index;
+        let recovery = self
+            .row_recoveries
+            .get_mut(&proposer_index)
+            .ok_or(AcknowledgmentFault::MissingPart)?;
+        let val = deserialize::<FieldWrap<Fr>>(ser_val)
+            .map_err(|_| AcknowledgmentFault::DeserializeValue)?
+            .into_inner();
+        if recovery
+            .commitment
+            .evaluate(our_index + 1, sender_index + 1)
+            != G1Affine::one().mul(val)
+        {
+            debug!(
+                "Value from {:?} does not match the overheard commitment of {:?}",
+                sender_index, proposer_index
+            );
+            return Ok(None);
+        }
+        let _ = recovery.values.insert(sender_index + 1, val);
+        if recovery.values.len() <= self.threshold {
+            return Ok(None);
+        }
+
+        let row = Poly::interpolate(recovery.values.iter().take(self.threshold + 1));
+        if row.commitment() != recovery.commitment.row(our_index + 1) {
+            return Ok(None);
+        }
+        let recovery = self
+            .row_recoveries
+            .remove(&proposer_index)
+            .ok_or(AcknowledgmentFault::MissingPart)?;
+        let _ = self
+            .parts
+            .insert(proposer_index, recovery.into_proposal_state());
+        self.events.push(DkgEvent::RowRecovered {
+            from: proposer_index,
+        });
+        Ok(Some(row))
+    }
+
+    /// Handles an acknowledgment. Returns our row of the acknowledged part if the acknowledgment
+    /// completed its recovery.
     fn handle_ack_or_fault(
         &mut self,
         sender_index: u64,
         Acknowledgment(proposer_index, receiver_index, ser_val, values): Acknowledgment,
-    ) -> Result<(), AcknowledgmentFault> {
+    ) -> Result<Option<Poly>, AcknowledgmentFault> {
         if values.len() != self.names.len() {
             return Err(AcknowledgmentFault::ValueCount);
         }
         if receiver_index != self.our_index {
-            return Ok(());
+            return Ok(None);
+        }
+        if !self.parts.contains_key(&proposer_index) {
+            return self.recover_row(proposer_index, sender_index, &ser_val);
         }
         {
             let part = self
                 .parts
                 .get_mut(&proposer_index)
                 .ok_or(AcknowledgmentFault::MissingPart)?;
-            if !part.acks.insert(sender_index) {
-                return Ok(()); // We already handled this `Acknowledgment` before.
+            if part.acks.contains(&sender_index) {
+                return Ok(None); // We already handled this `Acknowledgment` before.
             }
             let our_index = self.our_index;
 
@@ -1204,18 +2265,17 @@ impl KeyGen {
             {
                 return Err(AcknowledgmentFault::ValueAcknowledgment);
             }
+            // Only verified acknowledgments count towards completing the part, as the keys are
+            // interpolated from their values.
+            let _ = part.acks.insert(sender_index);
             let _ = part.values.insert(sender_index + 1, val);
-        }
-
-        {
-            let part = self
-                .parts
-                .get_mut(&sender_index)
-                .ok_or(AcknowledgmentFault::MissingPart)?;
             part.enc_values = values;
         }
-
-        Ok(())
+        self.events.push(DkgEvent::AckAccepted {
+            from: sender_index,
+            for_part: proposer_index,
+        });
+        Ok(None)
     }
 
     pub fn add_xornames(&mut self, xornames: Vec<XorName>) {
@@ -1230,6 +2290,61 @@ impl KeyGen {
     }
 }
 
+// Creates our bivariate polynomial for a round in the given mode.
+fn new_bivar_poly<R: RngCore>(rng: &mut R, threshold: usize, mode: &Mode) -> BivarPoly {
+    let mut rng = rng_adapter::RngAdapter(&mut *rng);
+    match mode {
+        // If this in an initial keygen, we generate a new random bivariate polynomial,
+        // including a new constant term.
+        Mode::Initial => BivarPoly::random(threshold, &mut rng),
+        // If it is a refresh, we generate a new random bivariate polynomial, but with
+        // zero constant term.
+        Mode::Refresh => BivarPoly::random_zeroconstant(threshold, &mut rng),
+        // If it is a recovery, we generate a new random bivariate polynomial that is zero at the
+        // recovered share index.
+        Mode::Recovery(shareindex) => {
+            let r: Fr = (shareindex + 1).into_fr();
+            BivarPoly::random_zero_at(threshold, r, &mut rng)
+        }
+    }
+}
+
+// Returns the message a proposer signs with the constant term of its poly, binding the proof to
+// the DKG session and to the proposer.
+fn proof_of_possession_msg(
+    context: &ShareXorName,
+    proposer: u64,
+    commitment: &BivarCommitment,
+) -> Result<Vec<u8>, Error> {
+    let constant = PublicKeySet::from(commitment.row(0)).public_key();
+    Ok(serialize(&(context.get_keygenid(), proposer, constant))?)
+}
+
+// Returns how many messages we buffer per sender and phase: a sender legitimately sends us a
+// `Proposal` for each member, and an `Acknowledgment` for each member and part, during a phase.
+// All buffers together hold as many messages as every member filling one of them.
+fn pending_messages(members: usize) -> PendingMessages {
+    let capacity = members * (members + 1);
+    PendingMessages::new(capacity, members * capacity)
+}
+
+// Returns the digest binding the phase of the sender of a `SyncResponse` to the messages it
+// carries for the requester. It is unkeyed: the sender signs it to vouch for the response.
+fn sync_digest(
+    context: &ShareXorName,
+    requester: u64,
+    phase: Phase,
+    messages: &[Message],
+) -> Result<[u8; 32], Error> {
+    let content = serialize(&(context.get_keygenid(), requester, phase, messages))?;
+    Ok(XorName::from_content(&content).0)
+}
+
+// Returns the digest identifying a `PublicKeySet` in `Finalized` messages.
+fn public_key_set_digest_of(public_key_set: &PublicKeySet) -> Result<[u8; 32], Error> {
+    Ok(XorName::from_content(&serialize(public_key_set)?).0)
+}
+
 impl Debug for KeyGen {
     fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
         write!(
@@ -1259,20 +2374,37 @@ impl KeyGen {
         phase: Phase,
     ) -> KeyGen {
         assert!(names.len() >= threshold);
+        let policy = DkgPolicy::default();
         KeyGen {
             our_id,
             our_index,
-            context,
+            identity: Identity::new(SecretKey::random(), BTreeMap::new()),
+            context: context.clone(),
             names: names.clone(),
-            encryptor: Encryptor::new(&names),
+            encryptor: Encryptor::new(&mut rand::thread_rng(), &names),
             parts: BTreeMap::new(),
+            row_recoveries: BTreeMap::new(),
             threshold,
             phase,
-            initalization_accumulator: InitializationAccumulator::new(),
-            complaints_accumulator: ComplaintsAccumulator::new(names, threshold),
+            phase_started: Instant::now(),
+            initalization_accumulator: InitializationAccumulator::new(
+                context,
+                policy.initialization,
+            ),
+            complaints_accumulator: ComplaintsAccumulator::new(names.clone(), threshold, policy),
+            qualification_accumulator: QualificationAccumulator::default(),
+            confirmation_accumulator: ConfirmationAccumulator::default(),
+            abort_accumulator: AbortAccumulator::default(),
             pending_complain_messages: Vec::new(),
-            pending_messages: Vec::new(),
+            complaint_reasons: BTreeMap::new(),
+            pending_messages: pending_messages(names.len()),
+            sent_messages: BTreeMap::new(),
+            resends: BTreeMap::new(),
+            syncs: BTreeMap::new(),
+            round_finished: false,
             mode: Mode::Initial,
+            policy,
+            events: Vec::new(),
         }
     }
 }
@@ -1321,4 +2453,10 @@ pub enum PartFault {
     /// Row does not match the ack.
     #[error("Row does not match the ack")]
     RowAcknowledgment,
+    /// The proof of possession of the constant term is invalid.
+    #[error("The proof of possession of the constant term is invalid")]
+    ProofOfPossession,
+    /// The commitment is not zero where the mode of the session requires it.
+    #[error("The commitment is not zero where the mode of the session requires it")]
+    Mode,
 }
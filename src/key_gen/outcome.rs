@@ -9,7 +9,10 @@
 
 use std::fmt::{self, Debug, Formatter};
 
-use crate::{PublicKeySet, SecretKeyShare};
+use crate::{Error, PublicKeySet, SecretKeyShare, Signature};
+use bincode::{deserialize, serialize};
+use blsttc::serde_impl::SerdeSecret;
+use serde_derive::{Deserialize, Serialize};
 
 #[derive(Clone)]
 /// DKG result
@@ -35,6 +38,33 @@ impl Outcome {
             index,
         }
     }
+
+    /// Serialises the outcome, INCLUDING the secret key share, for storage.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        Ok(serialize(&StoredOutcome {
+            public_key_set: self.public_key_set.clone(),
+            secret_key_share: SerdeSecret(self.secret_key_share.clone()),
+            index: self.index as u64,
+        })?)
+    }
+
+    /// Deserialises an outcome stored with `to_bytes`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Outcome, Error> {
+        let stored: StoredOutcome = deserialize(bytes)?;
+        Ok(Outcome::new(
+            stored.public_key_set,
+            stored.secret_key_share.into_inner(),
+            stored.index as usize,
+        ))
+    }
+}
+
+// The storage format of an `Outcome`, with a fixed size index.
+#[derive(Serialize, Deserialize)]
+struct StoredOutcome {
+    public_key_set: PublicKeySet,
+    secret_key_share: SerdeSecret<SecretKeyShare>,
+    index: u64,
 }
 
 impl Debug for Outcome {
@@ -46,3 +76,36 @@ impl Debug for Outcome {
         )
     }
 }
+
+#[derive(Clone)]
+/// DKG result confirmed by the group
+pub struct ConfirmedOutcome {
+    /// Our DKG result
+    pub outcome: Outcome,
+    /// Group signature over the new public key, combined from the members' signature shares.
+    /// It proves that at least threshold + 1 members derived the same key.
+    pub signature: Signature,
+}
+
+impl ConfirmedOutcome {
+    /// Create ConfirmedOutcome from components
+    pub fn new(outcome: Outcome, signature: Signature) -> Self {
+        Self { outcome, signature }
+    }
+
+    /// Returns `true` if the signature verifies against the group public key.
+    pub fn verify(&self) -> bool {
+        let public_key = self.outcome.public_key_set.public_key();
+        public_key.verify(&self.signature, public_key.to_bytes())
+    }
+}
+
+impl Debug for ConfirmedOutcome {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "ConfirmedOutcome({:?}, {:?})",
+            self.outcome, self.signature
+        )
+    }
+}
@@ -0,0 +1,350 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// https://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+use super::identity::Identity;
+use super::mode::Mode;
+use super::policy::DkgPolicy;
+use super::sharexorname::ShareXorName;
+use super::Error;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::convert::TryFrom;
+use xor_name::XorName;
+
+/// The validated parameters of a DKG session.
+///
+/// Following the `m of n` terminology, `threshold` is the degree of the generated polynomial: any
+/// `threshold + 1` of the `n` members can sign or decrypt for the group.
+///
+/// The parameters hold our [`Identity`], whose secret key is serialized along with them.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "UncheckedParams")]
+pub struct DkgParams {
+    our_id: XorName,
+    context: ShareXorName,
+    threshold: usize,
+    names: BTreeSet<XorName>,
+    mode: Mode,
+    policy: DkgPolicy,
+    identity: Identity,
+}
+
+// The serialized form of `DkgParams`, validated again once deserialized.
+#[derive(Deserialize)]
+struct UncheckedParams {
+    our_id: XorName,
+    context: ShareXorName,
+    threshold: usize,
+    names: BTreeSet<XorName>,
+    mode: Mode,
+    policy: DkgPolicy,
+    identity: Identity,
+}
+
+impl TryFrom<UncheckedParams> for DkgParams {
+    type Error = Error;
+
+    fn try_from(params: UncheckedParams) -> Result<Self, Error> {
+        DkgParams::builder(params.our_id, params.context, params.threshold)
+            .names(params.names)
+            .mode(params.mode)
+            .policy(params.policy)
+            .identity(params.identity)
+            .build()
+    }
+}
+
+impl DkgParams {
+    /// Starts building the parameters for a session among the members of `context`.
+    pub fn builder(our_id: XorName, context: ShareXorName, threshold: usize) -> DkgParamsBuilder {
+        DkgParamsBuilder {
+            our_id,
+            context,
+            threshold,
+            names: None,
+            mode: Mode::Initial,
+            policy: DkgPolicy::default(),
+            identity: None,
+        }
+    }
+
+    pub fn our_id(&self) -> XorName {
+        self.our_id
+    }
+
+    pub fn context(&self) -> &ShareXorName {
+        &self.context
+    }
+
+    pub fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    pub fn names(&self) -> &BTreeSet<XorName> {
+        &self.names
+    }
+
+    pub fn mode(&self) -> &Mode {
+        &self.mode
+    }
+
+    pub fn policy(&self) -> &DkgPolicy {
+        &self.policy
+    }
+
+    pub fn identity(&self) -> &Identity {
+        &self.identity
+    }
+
+    /// The number of faulty members the session tolerates while still collecting the
+    /// `threshold + 1` complete parts required to generate the keys.
+    pub fn max_faults(&self) -> usize {
+        self.names.len() - self.threshold - 1
+    }
+}
+
+/// Builder of [`DkgParams`].
+pub struct DkgParamsBuilder {
+    our_id: XorName,
+    context: ShareXorName,
+    threshold: usize,
+    names: Option<BTreeSet<XorName>>,
+    mode: Mode,
+    policy: DkgPolicy,
+    identity: Option<Identity>,
+}
+
+impl DkgParamsBuilder {
+    /// Sets the members of the session. Defaults to the names of the context, and must match
+    /// them if set.
+    pub fn names(mut self, names: BTreeSet<XorName>) -> Self {
+        self.names = Some(names);
+        self
+    }
+
+    /// Sets the mode of the session. Defaults to `Mode::Initial`.
+    pub fn mode(mut self, mode: Mode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Sets the quorum and exclusion rules of the session. Defaults to `DkgPolicy::default()`.
+    pub fn policy(mut self, policy: DkgPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Sets our key pair and the public keys of the members, required to sign and verify the
+    /// votes of the session.
+    pub fn identity(mut self, identity: Identity) -> Self {
+        self.identity = Some(identity);
+        self
+    }
+
+    /// Validates and returns the parameters.
+    pub fn build(self) -> Result<DkgParams, Error> {
+        let mut context_names = BTreeSet::new();
+        for name in self.context.xornames.iter() {
+            if !context_names.insert(*name) {
+                return Err(Error::DuplicateMember(*name));
+            }
+        }
+        let mut shares = BTreeSet::new();
+        for share in self.context.shares.iter() {
+            if !shares.insert(*share) {
+                return Err(Error::DuplicateShare(*share));
+            }
+        }
+
+        let names = self.names.unwrap_or_else(|| context_names.clone());
+        if names != context_names {
+            return Err(Error::MembersMismatch {
+                names,
+                context: context_names,
+            });
+        }
+        if !names.contains(&self.our_id) {
+            return Err(Error::NotAMember(self.our_id));
+        }
+        validate_threshold(self.threshold, names.len())?;
+        self.policy.validate(names.len(), self.threshold)?;
+        let identity = self.identity.ok_or(Error::MissingIdentity)?;
+        identity.validate(self.our_id, &names)?;
+
+        Ok(DkgParams {
+            our_id: self.our_id,
+            context: self.context,
+            threshold: self.threshold,
+            names,
+            mode: self.mode,
+            policy: self.policy,
+            identity,
+        })
+    }
+}
+
+// A member is excluded once more than `n - t` members complain against it. For the honest members
+// alone to reach that many while up to `n - t - 1` members are faulty, `t + 1 > n - t` must hold.
+// In addition, `0 < t < n` so that a signature needs more than one member and at least one member
+// may fail.
+fn validate_threshold(t: usize, n: usize) -> Result<(), Error> {
+    if t == 0 || t >= n || 2 * t < n {
+        return Err(Error::InvalidThreshold { t, n });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DkgParams, DkgParamsBuilder};
+    use crate::key_gen::identity::Identity;
+    use crate::key_gen::policy::{DkgPolicy, Quorum};
+    use crate::key_gen::{mode::Mode, sharexorname::ShareXorName, Error};
+    use blsttc::SecretKey;
+    use std::collections::{BTreeMap, BTreeSet};
+    use xor_name::XorName;
+
+    fn context_of(count: usize) -> (Vec<XorName>, ShareXorName) {
+        let names: Vec<XorName> = (0..count).map(|_| XorName::random()).collect();
+        let context = ShareXorName::from_xornames(names.clone());
+        (names, context)
+    }
+
+    // Starts building the parameters of `our_id`, with a key pair for each member of `context`.
+    fn builder(our_id: XorName, context: ShareXorName, threshold: usize) -> DkgParamsBuilder {
+        let secret_key = SecretKey::random();
+        let public_keys = context
+            .xornames
+            .iter()
+            .map(|name| {
+                let public_key = if *name == our_id {
+                    secret_key.public_key()
+                } else {
+                    SecretKey::random().public_key()
+                };
+                (*name, public_key)
+            })
+            .collect();
+        DkgParams::builder(our_id, context, threshold)
+            .identity(Identity::new(secret_key, public_keys))
+    }
+
+    #[test]
+    fn valid_params() {
+        let (names, context) = context_of(7);
+        let params = builder(names[0], context, 5)
+            .mode(Mode::Refresh)
+            .build()
+            .expect("valid params");
+        assert_eq!(params.names().len(), 7);
+        assert_eq!(params.mode(), &Mode::Refresh);
+        assert_eq!(params.max_faults(), 1);
+    }
+
+    #[test]
+    fn invalid_thresholds() {
+        let (names, context) = context_of(7);
+        for t in [0, 3, 7, 8].iter() {
+            assert_eq!(
+                builder(names[0], context.clone(), *t).build(),
+                Err(Error::InvalidThreshold { t: *t, n: 7 })
+            );
+        }
+    }
+
+    #[test]
+    fn names_must_match_context() {
+        let (names, context) = context_of(4);
+        let mut others: BTreeSet<XorName> = names.iter().cloned().collect();
+        let _ = others.remove(&names[3]);
+        let _ = others.insert(XorName::random());
+        match builder(names[0], context.clone(), 2).names(others).build() {
+            Err(Error::MembersMismatch { .. }) => {}
+            other => panic!("Unexpected result {:?}", other),
+        }
+
+        let stranger = XorName::random();
+        assert_eq!(
+            builder(stranger, context, 2).build(),
+            Err(Error::NotAMember(stranger))
+        );
+    }
+
+    #[test]
+    fn invalid_policy_is_rejected() {
+        let (names, context) = context_of(7);
+        let policy = DkgPolicy {
+            exclusion: Quorum::Majority,
+            ..DkgPolicy::default()
+        };
+        assert_eq!(
+            builder(names[0], context.clone(), 5)
+                .policy(policy)
+                .build()
+                .map(|params| *params.policy()),
+            Ok(policy)
+        );
+
+        let policy = DkgPolicy {
+            exclusion: Quorum::AtLeast(8),
+            ..DkgPolicy::default()
+        };
+        assert_eq!(
+            builder(names[0], context, 5).policy(policy).build(),
+            Err(Error::InvalidQuorum {
+                rule: "exclusion",
+                required: 8,
+                n: 7
+            })
+        );
+    }
+
+    #[test]
+    fn duplicated_members_are_rejected() {
+        let (names, mut context) = context_of(4);
+        // `from_xornames` drops duplicates, so add one by hand.
+        context.xornames.push(names[0]);
+        context.shares.push(4);
+        assert_eq!(
+            builder(names[1], context, 3).build(),
+            Err(Error::DuplicateMember(names[0]))
+        );
+    }
+
+    #[test]
+    fn identity_must_cover_the_members() {
+        let (names, context) = context_of(4);
+        assert_eq!(
+            DkgParams::builder(names[0], context.clone(), 2).build(),
+            Err(Error::MissingIdentity)
+        );
+
+        let secret_key = SecretKey::random();
+        let mut public_keys: BTreeMap<XorName, _> = names
+            .iter()
+            .map(|name| (*name, SecretKey::random().public_key()))
+            .collect();
+        let identity = |public_keys| Identity::new(secret_key.clone(), public_keys);
+        assert_eq!(
+            DkgParams::builder(names[0], context.clone(), 2)
+                .identity(identity(public_keys.clone()))
+                .build(),
+            Err(Error::IdentityMismatch(names[0]))
+        );
+
+        let _ = public_keys.insert(names[0], secret_key.public_key());
+        let _ = public_keys.remove(&names[3]);
+        assert_eq!(
+            DkgParams::builder(names[0], context, 2)
+                .identity(identity(public_keys))
+                .build(),
+            Err(Error::MissingPublicKey(names[3]))
+        );
+    }
+}
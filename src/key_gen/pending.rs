@@ -0,0 +1,162 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// https://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+use super::message::Message;
+use super::Phase;
+use std::collections::{BTreeMap, VecDeque};
+
+/// Messages we can't handle yet, buffered by the phase they wait for and by sender. Each buffer
+/// holds at most `capacity` messages, and all of them at most `total_capacity`, so that senders
+/// can't make us grow without limit.
+pub(crate) struct PendingMessages {
+    buffers: BTreeMap<(Phase, u64), VecDeque<Message>>,
+    capacity: usize,
+    total_capacity: usize,
+    len: usize,
+    dropped: usize,
+}
+
+impl PendingMessages {
+    pub(crate) fn new(capacity: usize, total_capacity: usize) -> Self {
+        PendingMessages {
+            buffers: BTreeMap::new(),
+            capacity,
+            total_capacity,
+            len: 0,
+            dropped: 0,
+        }
+    }
+
+    /// Buffers `msg` until we reach `phase`. Returns `false` if the buffer of its sender for that
+    /// phase, or all the buffers together, are full, in which case the message is dropped.
+    pub(crate) fn push(&mut self, phase: Phase, msg: Message) -> bool {
+        if self.len >= self.total_capacity {
+            self.dropped += 1;
+            return false;
+        }
+        let buffer = self.buffers.entry((phase, msg.key_gen_id())).or_default();
+        if buffer.len() >= self.capacity {
+            self.dropped += 1;
+            return false;
+        }
+        buffer.push_back(msg);
+        self.len += 1;
+        true
+    }
+
+    /// Records a message dropped without being buffered, e.g. as its phase has passed.
+    pub(crate) fn drop_stale(&mut self) {
+        self.dropped += 1;
+    }
+
+    /// Removes and returns the messages waiting for `phase` or an earlier one, in phase order.
+    pub(crate) fn take_ready(&mut self, phase: Phase) -> Vec<Message> {
+        let ready: Vec<_> = self
+            .buffers
+            .range(..=(phase, u64::MAX))
+            .map(|(key, _)| *key)
+            .collect();
+        let msgs: Vec<_> = ready
+            .into_iter()
+            .filter_map(|key| self.buffers.remove(&key))
+            .flatten()
+            .collect();
+        self.len -= msgs.len();
+        msgs
+    }
+
+    /// Drops the messages waiting for a phase before `phase`.
+    pub(crate) fn drop_before(&mut self, phase: Phase) {
+        let later = self.buffers.split_off(&(phase, 0));
+        let stale = std::mem::replace(&mut self.buffers, later);
+        let count = stale.values().map(VecDeque::len).sum::<usize>();
+        self.len -= count;
+        self.dropped += count;
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.buffers.clear();
+        self.len = 0;
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &Message> {
+        self.buffers.values().flatten()
+    }
+
+    /// The number of messages dropped so far.
+    pub(crate) fn dropped(&self) -> usize {
+        self.dropped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sharexorname::ShareXorName;
+    use xor_name::XorName;
+
+    fn complaint(key_gen_id: u64) -> Message {
+        Message::Complaint {
+            key_gen_id,
+            target: 0,
+            context: ShareXorName::from_xornames(vec![XorName::default()]),
+            msg: Vec::new(),
+        }
+    }
+
+    fn senders(msgs: &[Message]) -> Vec<u64> {
+        msgs.iter().map(Message::key_gen_id).collect()
+    }
+
+    #[test]
+    fn only_reachable_phases_are_taken() {
+        let mut pending = PendingMessages::new(2, 10);
+        assert!(pending.push(Phase::Commitment, complaint(3)));
+        assert!(pending.push(Phase::Complaining, complaint(2)));
+        assert!(pending.push(Phase::Complaining, complaint(1)));
+        assert!(pending.push(Phase::Complaining, complaint(1)));
+        assert!(!pending.push(Phase::Complaining, complaint(1)));
+        assert_eq!(pending.dropped(), 1);
+
+        assert!(pending.take_ready(Phase::Contribution).is_empty());
+        assert_eq!(
+            senders(&pending.take_ready(Phase::Complaining)),
+            vec![1, 1, 2]
+        );
+        assert_eq!(senders(&pending.take_ready(Phase::Finalization)), vec![3]);
+    }
+
+    #[test]
+    fn passed_phases_are_dropped() {
+        let mut pending = PendingMessages::new(2, 10);
+        assert!(pending.push(Phase::Complaining, complaint(1)));
+        assert!(pending.push(Phase::Commitment, complaint(2)));
+        pending.drop_before(Phase::Commitment);
+        assert_eq!(pending.dropped(), 1);
+        assert_eq!(senders(&pending.take_ready(Phase::Commitment)), vec![2]);
+    }
+
+    #[test]
+    fn all_buffers_are_bounded_together() {
+        let mut pending = PendingMessages::new(2, 3);
+        assert!(pending.push(Phase::Complaining, complaint(1)));
+        assert!(pending.push(Phase::Complaining, complaint(2)));
+        assert!(pending.push(Phase::Commitment, complaint(3)));
+        assert!(!pending.push(Phase::Commitment, complaint(4)));
+        assert_eq!(pending.dropped(), 1);
+
+        // Taking messages out makes room again.
+        assert_eq!(senders(&pending.take_ready(Phase::Complaining)), vec![1, 2]);
+        assert!(pending.push(Phase::Commitment, complaint(4)));
+        pending.clear();
+        assert!(pending.push(Phase::Commitment, complaint(5)));
+        assert!(pending.push(Phase::Commitment, complaint(6)));
+        assert!(pending.push(Phase::Commitment, complaint(7)));
+    }
+}
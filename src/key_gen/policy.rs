@@ -0,0 +1,174 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// https://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+use super::Error;
+use serde_derive::{Deserialize, Serialize};
+
+/// A number of members required by a rule of the protocol, for `n` members and a threshold `t`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Quorum {
+    /// At least `t` members.
+    Threshold,
+    /// More than `n - t` members.
+    AboveNonThreshold,
+    /// More than half of the members.
+    Majority,
+    /// At least the given number of members.
+    AtLeast(usize),
+}
+
+impl Quorum {
+    /// Returns how many members the quorum requires.
+    pub fn required(&self, n: usize, t: usize) -> usize {
+        match self {
+            Quorum::Threshold => t,
+            Quorum::AboveNonThreshold => n.saturating_sub(t) + 1,
+            Quorum::Majority => n / 2 + 1,
+            Quorum::AtLeast(count) => *count,
+        }
+    }
+}
+
+/// The rules deciding when a session starts and which members get excluded from it.
+///
+/// The defaults are the rules the protocol always applied, and suit up to `n - t - 1` faulty
+/// members. Every member of a session must use the same policy.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DkgPolicy {
+    /// Matching `Initialization` messages required to start the session, counted against the
+    /// proposed threshold. Defaults to `Quorum::Threshold`.
+    pub initialization: Quorum,
+    /// Complaints against a member required to exclude it. Defaults to
+    /// `Quorum::AboveNonThreshold`.
+    pub exclusion: Quorum,
+    /// Exclusions a member must have failed to complain about to get excluded as well. Defaults
+    /// to `Quorum::Majority`.
+    pub missed_complaints: Quorum,
+    /// Times a proposer must have missed acknowledging its own part to be complained against
+    /// as a non-contributor. Defaults to `Quorum::Majority`.
+    pub non_contribution: Quorum,
+    /// `Abort` messages blaming the same members required to abort the session, and at least a
+    /// majority of the members in any case. Defaults to `Quorum::AboveNonThreshold`.
+    pub abort: Quorum,
+}
+
+impl Default for DkgPolicy {
+    fn default() -> Self {
+        DkgPolicy {
+            initialization: Quorum::Threshold,
+            exclusion: Quorum::AboveNonThreshold,
+            missed_complaints: Quorum::Majority,
+            non_contribution: Quorum::Majority,
+            abort: Quorum::AboveNonThreshold,
+        }
+    }
+}
+
+impl DkgPolicy {
+    /// Checks that the rules can be applied to `n` members with a threshold `t`.
+    ///
+    /// Every rule must require at least one and at most `n` members. The honest members must be
+    /// able to start the session, to exclude a member and to abort on their own, while the up to
+    /// `n - t - 1` faulty ones must not be able to exclude anyone or to abort by themselves.
+    pub fn validate(&self, n: usize, t: usize) -> Result<(), Error> {
+        let faulty = n.saturating_sub(t + 1);
+        let rules = [
+            ("initialization", self.initialization),
+            ("exclusion", self.exclusion),
+            ("missed_complaints", self.missed_complaints),
+            ("non_contribution", self.non_contribution),
+            ("abort", self.abort),
+        ];
+        for (rule, quorum) in rules.iter() {
+            let required = quorum.required(n, t);
+            if required == 0 || required > n {
+                return Err(Error::InvalidQuorum { rule, required, n });
+            }
+        }
+        let honest = n - faulty;
+        let initialization = self.initialization.required(n, t);
+        if initialization > honest {
+            return Err(Error::InvalidQuorum {
+                rule: "initialization",
+                required: initialization,
+                n,
+            });
+        }
+        for (rule, quorum) in [("exclusion", self.exclusion), ("abort", self.abort)].iter() {
+            let required = quorum.required(n, t);
+            if required > honest || required <= faulty {
+                return Err(Error::InvalidQuorum { rule, required, n });
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DkgPolicy, Quorum};
+    use crate::key_gen::Error;
+
+    #[test]
+    fn defaults_keep_the_protocol_rules() {
+        let policy = DkgPolicy::default();
+        assert_eq!(policy.initialization.required(7, 5), 5);
+        assert_eq!(policy.exclusion.required(7, 5), 3);
+        assert_eq!(policy.missed_complaints.required(7, 5), 4);
+        assert_eq!(policy.non_contribution.required(8, 5), 5);
+        assert_eq!(policy.abort.required(7, 5), 3);
+        for (n, t) in [(4, 2), (5, 3), (7, 5), (10, 5)].iter() {
+            assert_eq!(policy.validate(*n, *t), Ok(()));
+        }
+    }
+
+    #[test]
+    fn unreachable_or_unsafe_quorums_are_rejected() {
+        let policy = DkgPolicy {
+            missed_complaints: Quorum::AtLeast(0),
+            ..DkgPolicy::default()
+        };
+        assert_eq!(
+            policy.validate(7, 5),
+            Err(Error::InvalidQuorum {
+                rule: "missed_complaints",
+                required: 0,
+                n: 7
+            })
+        );
+
+        // With one faulty member, a single complaint must not be enough to exclude.
+        let policy = DkgPolicy {
+            exclusion: Quorum::AtLeast(1),
+            ..DkgPolicy::default()
+        };
+        assert!(policy.validate(7, 5).is_err());
+
+        // Likewise, the faulty member must not be able to abort the session alone.
+        let policy = DkgPolicy {
+            abort: Quorum::AtLeast(1),
+            ..DkgPolicy::default()
+        };
+        assert_eq!(
+            policy.validate(7, 5),
+            Err(Error::InvalidQuorum {
+                rule: "abort",
+                required: 1,
+                n: 7
+            })
+        );
+
+        // The honest members alone can't gather all 7 initializations.
+        let policy = DkgPolicy {
+            initialization: Quorum::AtLeast(7),
+            ..DkgPolicy::default()
+        };
+        assert!(policy.validate(7, 5).is_err());
+    }
+}
@@ -0,0 +1,37 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// https://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+use super::message::MessageKind;
+use super::Phase;
+use std::collections::{BTreeMap, BTreeSet};
+use xor_name::XorName;
+
+/// A snapshot of the state of a `KeyGen`, to diagnose a stalled DKG session.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DkgProgress {
+    /// Current DKG phase.
+    pub phase: Phase,
+    /// The degree of the generated polynomial.
+    pub threshold: usize,
+    /// The proposers whose part we hold.
+    pub parts_held: BTreeSet<u64>,
+    /// The number of acknowledgments received for each part, indexed by proposer.
+    pub ack_counts: BTreeMap<u64, usize>,
+    /// The number of parts acknowledged by more than `threshold` members.
+    pub complete_parts: usize,
+    /// The number of messages we cannot handle yet, by type.
+    pub pending_messages: BTreeMap<MessageKind, usize>,
+    /// The number of messages dropped as their sender's buffer was full, or as the phase they
+    /// waited for had passed.
+    pub dropped_messages: usize,
+    /// The number of members complaining against each target.
+    pub complaints: BTreeMap<XorName, usize>,
+    /// The members we are waiting for, see `KeyGen::possible_blockers`.
+    pub blockers: BTreeSet<XorName>,
+}
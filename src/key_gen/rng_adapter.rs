@@ -10,7 +10,7 @@
 use rand::Rng;
 use rand_core::RngCore;
 
-pub(super) struct RngAdapter<'a, T: ?Sized>(pub &'a mut T);
+pub(crate) struct RngAdapter<'a, T: ?Sized>(pub &'a mut T);
 
 impl<'a, T> RngCore for RngAdapter<'a, T>
 where
@@ -1,12 +1,10 @@
 use serde_derive::{Deserialize, Serialize};
-use std::collections::HashSet;
-use std::iter::FromIterator;
-use xor_name::xor_name;
+use std::collections::BTreeSet;
 use xor_name::XorName;
 
-/// In bls_dkg, it is assumed the u64 index of a node is constant and can be derived from a constant
-/// list of XorNames, with the index the position in the sorted list.  This index is cast to Fr
-/// and polynomials evaluated at it.  We replace this u64 with a wrapper struct that tracks the context.
+// In bls_dkg, it is assumed the u64 index of a node is constant and can be derived from a constant
+// list of XorNames, with the index the position in the sorted list.  This index is cast to Fr
+// and polynomials evaluated at it.  We replace this u64 with a wrapper struct that tracks the context.
 // #[derive(Debug, Clone, PartialEq, Eq,Ord)]
 // pub struct IndexWithContext {
 //     pub my_share: u64,
@@ -35,11 +33,11 @@ impl ShareXorName {
     // note that this gives a different assignment of share than inserting
     // the xornames one by one; this is lexicogrphic.
     pub fn from_xornames(xornames: Vec<XorName>) -> ShareXorName {
-        // TODO assert all unique
         // TODO assert not too many (less than field max to avoid assigning zero)
-        let length = xornames.len();
         let mut xornames = xornames;
         xornames.sort();
+        xornames.dedup();
+        let length = xornames.len();
         ShareXorName {
             xornames,
             shares: (0..length).map(|x| x as u64).collect(),
@@ -112,43 +110,36 @@ impl ShareXorName {
         self.available.sort_by(|a, b| b.cmp(a)); // sort() and reverse()
                                                  // no sort of xornames and shares is needed
     }
+    // add an xorname if not present, reusing the lowest available share, or else taking the
+    // share after the highest one ever assigned
     fn add_xorname(&mut self, xorname: XorName) {
-        if let Some(share) = self.available.pop() {
-            self.xornames.push(xorname);
-            self.shares.push(share);
-        } else {
-            let share = (self.xornames.len() + 0) as u64; // +1 to take next open share
-            self.xornames.push(xorname);
-            self.shares.push(share);
-        }
-        self.sort();
-    }
-
-    fn iteradd_xornames(&mut self, add_xornames: Vec<XorName>) {
-        for xorname in add_xornames {
-            self.add_xorname(xorname)
+        if self.xornames.contains(&xorname) {
+            return;
         }
+        let share = match self.available.pop() {
+            Some(share) => share,
+            None => self
+                .shares
+                .iter()
+                .max()
+                .map_or(0, |max_share| max_share + 1),
+        };
+        self.xornames.push(xorname);
+        self.shares.push(share);
     }
 
+    // add xornames if not present, assigning shares in order of arrival
     pub fn add_xornames(&mut self, add_xornames: Vec<XorName>) {
-        let mut next_share = self.xornames.len() as u64;
         for xorname in add_xornames {
-            if let Some(share) = self.available.pop() {
-                self.xornames.push(xorname);
-                self.shares.push(share);
-            } else {
-                self.xornames.push(xorname);
-                self.shares.push(next_share);
-                next_share += 1;
-            }
+            self.add_xorname(xorname)
         }
         self.sort()
     }
 
+    // Names are added in lexicographic order, so that all nodes assign the same shares.
     pub fn to_new_xornames(&mut self, new_xornames: Vec<XorName>) {
-        let old: HashSet<XorName> = self.xornames.clone().into_iter().collect();
-        let new: HashSet<XorName> = new_xornames.into_iter().collect();
-        //	let to_keep = old.intersection(new); // not needed
+        let old: BTreeSet<XorName> = self.xornames.clone().into_iter().collect();
+        let new: BTreeSet<XorName> = new_xornames.into_iter().collect();
         let to_remove: Vec<XorName> = old.difference(&new).cloned().collect();
         let to_add: Vec<XorName> = new.difference(&old).cloned().collect();
         self.remove_xornames(to_remove);
@@ -161,20 +152,41 @@ impl ShareXorName {
     pub fn set_keygenid(&mut self, keygenid: [u8; 32]) {
         self.keygenid = keygenid;
     }
+
+    // Returns the context left after excluding the given names, with a keygenid derived from ours
+    // and from the excluded names, so that every node excluding the same names agrees on it while
+    // messages of the previous context no longer match.
+    pub fn excluding(&self, excluded: &BTreeSet<XorName>) -> ShareXorName {
+        let mut context = self.clone();
+        context.remove_xornames(excluded.iter().cloned().collect());
+        let mut content = self.keygenid.to_vec();
+        for name in excluded {
+            content.extend_from_slice(&name.0);
+        }
+        context.keygenid = XorName::from_content(&content).0;
+        context
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::ShareXorName;
+    use proptest::prelude::*;
+    use std::collections::{BTreeMap, BTreeSet};
     use xor_name::xor_name;
     use xor_name::XorName;
 
     #[test]
     fn test_gen_names() {
-        let names: Vec<XorName> = (1..10).map(|i| XorName::random()).collect();
-        let name2 = xor_name!(1);
-        let sxn = ShareXorName::from_xornames(names);
-        //println!("{:?}", sxn);
+        let names: Vec<XorName> = (1..10).map(|_| XorName::random()).collect();
+        let sxn = ShareXorName::from_xornames(names.clone());
+        let mut sorted = names;
+        sorted.sort();
+        assert_eq!(sxn.xornames, sorted);
+        assert_eq!(sxn.shares, (0..9).collect::<Vec<u64>>());
+        assert!(sxn.available.is_empty());
+        assert_eq!(sxn.get_share(sorted[3]), Some(3));
+        assert_eq!(sxn.get_xorname(3), Some(sorted[3]));
     }
 
     // The share assignment keeps assigned shares when possible.  So given an unsorted list of
@@ -187,35 +199,36 @@ mod tests {
 
     #[test]
     fn test_inorder() {
-        //let names: Vec<XorName> = (1..10).map(|i| XorName::random()).collect();
         let names: Vec<XorName> = (1..5).map(|i| xor_name!(i)).collect();
         let mut sxn2 = ShareXorName::from_xornames(Vec::new());
         let mut sxn3 = ShareXorName::from_xornames(Vec::new());
-        sxn2.add_xornames(names.clone()); // all at once (FIX)
-        sxn3.iteradd_xornames(names.clone()); // one at a time
-        println!("{:?}", sxn2);
+        sxn2.add_xornames(names.clone()); // all at once
+        for name in names.iter() {
+            sxn3.add_xornames(vec![*name]); // one at a time
+        }
         assert_eq!(sxn3, sxn2, "sxn3 {:?} sxn2 {:?}", sxn3, sxn2);
 
         let mut sxn = ShareXorName::from_xornames(names);
         assert_eq!(sxn, sxn2, "sxn1 {:?} sxn2 {:?}", sxn, sxn2);
 
-        println!("1 {:?}", sxn);
-        println!("2 {:?}", sxn2);
-
-        println!("{:?}", sxn);
-        sxn.add_xorname(XorName::random()); //name is inserted with new share
-        println!("{:?}", sxn);
-        sxn.remove_xorname(XorName::random()); //nothing happens unless a collision
-        println!("{:?}", sxn);
-        sxn.remove_xorname(sxn.xornames[2]); // something removed
-        println!("{:?}", sxn);
-        sxn.add_xorname(XorName::random()); // something addded, reusing existing share
-        println!("{:?}", sxn);
+        let added = XorName::random();
+        sxn.add_xornames(vec![added]); // name is inserted with a new share
+        assert_eq!(sxn.get_share(added), Some(4));
+        let before = sxn.clone();
+        sxn.remove_xorname(XorName::random()); // nothing happens unless a collision
+        assert_eq!(sxn, before);
+        let removed = sxn.xornames[2];
+        let removed_share = sxn.get_share(removed);
+        sxn.remove_xorname(removed); // something removed
+        assert_eq!(sxn.get_share(removed), None);
+        let added = XorName::random();
+        sxn.add_xornames(vec![added]); // something added, reusing the removed share
+        assert_eq!(sxn.get_share(added), removed_share);
+        assert!(sxn.available.is_empty());
     }
 
     #[test]
     fn test_not_in_order() {
-        //let names: Vec<XorName> = (1..10).map(|i| XorName::random()).collect();
         let names: Vec<XorName> = vec![12, 11, 15, 13, 14]
             .into_iter()
             .map(|i| xor_name!(i))
@@ -223,24 +236,168 @@ mod tests {
         let mut sxn2 = ShareXorName::from_xornames(Vec::new());
         let mut sxn3 = ShareXorName::from_xornames(Vec::new());
         sxn2.add_xornames(names.clone());
-        sxn3.iteradd_xornames(names.clone());
-        println!("{:?}", sxn2);
+        for name in names.iter() {
+            sxn3.add_xornames(vec![*name]);
+        }
         assert_eq!(sxn3, sxn2);
+        // In order of arrival.
+        assert_eq!(sxn2.get_share(xor_name!(12)), Some(0));
+        assert_eq!(sxn2.get_share(xor_name!(11)), Some(1));
 
-        let mut sxn = ShareXorName::from_xornames(names);
+        let sxn = ShareXorName::from_xornames(names);
         assert_ne!(sxn, sxn2);
+        // In lexicographic order.
+        assert_eq!(sxn.get_share(xor_name!(11)), Some(0));
+        assert_eq!(sxn.get_share(xor_name!(12)), Some(1));
+    }
+
+    #[test]
+    fn test_new_share_after_partial_reuse() {
+        let names: Vec<XorName> = (1..6).map(|i| xor_name!(i)).collect();
+        let mut sxn = ShareXorName::from_xornames(names.clone());
+        sxn.remove_xornames(vec![names[2]]);
+        // The first new name reuses share 2, the second must not collide with share 4.
+        sxn.add_xornames(vec![xor_name!(10), xor_name!(11)]);
+        assert_eq!(sxn.get_share(xor_name!(10)), Some(2));
+        assert_eq!(sxn.get_share(xor_name!(11)), Some(5));
+    }
 
-        println!("1 {:?}", sxn);
-        println!("2 {:?}", sxn2);
-
-        println!("{:?}", sxn);
-        sxn.add_xorname(XorName::random()); //name is inserted with new share
-        println!("{:?}", sxn);
-        sxn.remove_xorname(XorName::random()); //nothing happens unless a collision
-        println!("{:?}", sxn);
-        sxn.remove_xorname(sxn.xornames[2]); // something removed
-        println!("{:?}", sxn);
-        sxn.add_xorname(XorName::random()); // something addded, reusing existing share
-        println!("{:?}", sxn);
+    #[test]
+    fn test_excluding_derives_a_new_context() {
+        let names: Vec<XorName> = (1..6).map(|i| xor_name!(i)).collect();
+        let sxn = ShareXorName::from_xornames(names.clone());
+        let excluded: BTreeSet<XorName> = vec![names[1], names[3]].into_iter().collect();
+        let after = sxn.excluding(&excluded);
+        assert_eq!(after.xornames, vec![names[0], names[2], names[4]]);
+        assert_eq!(after.shares, vec![0, 2, 4]);
+        assert_ne!(after.keygenid, sxn.keygenid);
+        assert_eq!(after, sxn.excluding(&excluded));
+        let other: BTreeSet<XorName> = vec![names[1]].into_iter().collect();
+        assert_ne!(after.keygenid, sxn.excluding(&other).keygenid);
+    }
+
+    #[derive(Clone, Debug)]
+    enum Churn {
+        Add(Vec<XorName>),
+        Remove(Vec<XorName>),
+        ToNew(Vec<XorName>),
+    }
+
+    // Names are drawn from a small pool, so that churn often touches existing members.
+    fn name() -> impl Strategy<Value = XorName> {
+        (0u8..24).prop_map(|i| XorName([i; 32]))
+    }
+
+    fn names() -> impl Strategy<Value = Vec<XorName>> {
+        prop::collection::vec(name(), 0..12)
+    }
+
+    fn churn() -> impl Strategy<Value = Churn> {
+        prop_oneof![
+            names().prop_map(Churn::Add),
+            names().prop_map(Churn::Remove),
+            names().prop_map(Churn::ToNew),
+        ]
+    }
+
+    fn check_invariants(sxn: &ShareXorName) -> Result<(), TestCaseError> {
+        prop_assert_eq!(sxn.xornames.len(), sxn.shares.len());
+        let names: BTreeSet<_> = sxn.xornames.iter().collect();
+        prop_assert_eq!(names.len(), sxn.xornames.len(), "duplicated name");
+        let shares: BTreeSet<_> = sxn.shares.iter().collect();
+        prop_assert_eq!(shares.len(), sxn.shares.len(), "duplicated share");
+        prop_assert!(
+            sxn.available.windows(2).all(|pair| pair[0] > pair[1]),
+            "available pool not strictly decreasing: {:?}",
+            sxn.available
+        );
+        prop_assert!(sxn.available.iter().all(|share| !shares.contains(share)));
+        for (name, share) in sxn.get_pairs() {
+            prop_assert_eq!(sxn.get_share(name), Some(share));
+            prop_assert_eq!(sxn.get_xorname(share), Some(name));
+        }
+        Ok(())
+    }
+
+    fn share_map(sxn: &ShareXorName) -> BTreeMap<XorName, u64> {
+        sxn.get_pairs().into_iter().collect()
+    }
+
+    proptest! {
+        #[test]
+        fn from_xornames_assigns_shares_lexicographically(names in names()) {
+            let sxn = ShareXorName::from_xornames(names.clone());
+            check_invariants(&sxn)?;
+            let sorted: Vec<XorName> = names.into_iter().collect::<BTreeSet<_>>().into_iter().collect();
+            prop_assert_eq!(&sxn.xornames, &sorted);
+            prop_assert_eq!(sxn.shares, (0..sorted.len() as u64).collect::<Vec<_>>());
+        }
+
+        #[test]
+        fn churn_keeps_shares_unique_and_stable(
+            initial in names(),
+            churns in prop::collection::vec(churn(), 0..20),
+        ) {
+            let mut sxn = ShareXorName::from_xornames(initial);
+            check_invariants(&sxn)?;
+            for churn in churns {
+                let before = share_map(&sxn);
+                let available = sxn.available.clone();
+                let expected_names: BTreeSet<XorName> = match &churn {
+                    Churn::Add(added) => {
+                        sxn.add_xornames(added.clone());
+                        before.keys().chain(added.iter()).cloned().collect()
+                    }
+                    Churn::Remove(removed) => {
+                        sxn.remove_xornames(removed.clone());
+                        before.keys().filter(|name| !removed.contains(name)).cloned().collect()
+                    }
+                    Churn::ToNew(new) => {
+                        sxn.to_new_xornames(new.clone());
+                        new.iter().cloned().collect()
+                    }
+                };
+                check_invariants(&sxn)?;
+                let after = share_map(&sxn);
+                prop_assert_eq!(after.keys().cloned().collect::<BTreeSet<_>>(), expected_names);
+
+                // Remaining members keep their share.
+                for (name, share) in after.iter() {
+                    if let Some(old_share) = before.get(name) {
+                        prop_assert_eq!(share, old_share);
+                    }
+                }
+
+                // New members take the lowest available shares first.
+                let mut new_shares: Vec<u64> = after
+                    .iter()
+                    .filter(|(name, _)| !before.contains_key(name))
+                    .map(|(_, share)| *share)
+                    .collect();
+                new_shares.sort_unstable();
+                let mut reusable: Vec<u64> = available.iter().rev().cloned().collect();
+                if let Churn::ToNew(_) = churn {
+                    // Removed members' shares are returned to the pool before adding.
+                    reusable.extend(
+                        before
+                            .iter()
+                            .filter(|(name, _)| !after.contains_key(name))
+                            .map(|(_, share)| *share),
+                    );
+                    reusable.sort_unstable();
+                }
+                let reused = new_shares.len().min(reusable.len());
+                prop_assert_eq!(&new_shares[..reused], &reusable[..reused]);
+            }
+        }
+
+        #[test]
+        fn to_new_xornames_ignores_the_order_of_names(initial in names(), new in names()) {
+            let mut sxn = ShareXorName::from_xornames(initial.clone());
+            sxn.to_new_xornames(new.clone());
+            let mut reversed = ShareXorName::from_xornames(initial);
+            reversed.to_new_xornames(new.into_iter().rev().collect());
+            prop_assert_eq!(sxn, reversed);
+        }
     }
 }
@@ -0,0 +1,60 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// https://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+//! **NOT FOR PRODUCTION USE**: Alterations of the private content of messages, for the
+//! adversaries of `dev_utils`.
+
+use super::sharexorname::ShareXorName;
+use super::{proof_of_possession_msg, rng_adapter, Acknowledgment, Error, Part};
+use bincode::serialize;
+use blsttc::{
+    ff::Field,
+    poly::{BivarPoly, Poly},
+    serde_impl::FieldWrap,
+    Fr, SecretKey,
+};
+use rand::RngCore;
+
+/// Replaces the row of `part` with a random one, which no longer matches the commitment.
+pub(crate) fn corrupt_row<R: RngCore>(rng: &mut R, part: &mut Part) -> Result<(), Error> {
+    let mut rng = rng_adapter::RngAdapter(&mut *rng);
+    let row = Poly::random(part.commitment.degree(), &mut rng);
+    part.ser_row = serialize(&row)?;
+    Ok(())
+}
+
+/// Returns a valid `Part` for the same receiver as `part`, but of a new random polynomial, as if
+/// `proposer` proposed twice.
+pub(crate) fn forge_part<R: RngCore>(
+    rng: &mut R,
+    context: &ShareXorName,
+    proposer: u64,
+    part: &Part,
+) -> Result<Part, Error> {
+    let mut rng = rng_adapter::RngAdapter(&mut *rng);
+    let poly = BivarPoly::random(part.commitment.degree(), &mut rng);
+    let commitment = poly.commitment();
+    let pop_msg = proof_of_possession_msg(context, proposer, &commitment)?;
+    let mut constant = poly.evaluate(0, 0);
+    Ok(Part {
+        receiver: part.receiver,
+        context: part.context.clone(),
+        ser_row: serialize(&poly.row(part.receiver + 1))?,
+        commitment,
+        enc_rows: part.enc_rows.clone(),
+        proof: SecretKey::from_mut(&mut constant).sign(pop_msg),
+    })
+}
+
+/// Replaces the value acknowledged to the receiver of `ack` with a random one.
+pub(crate) fn corrupt_ack<R: RngCore>(rng: &mut R, ack: &mut Acknowledgment) -> Result<(), Error> {
+    let mut rng = rng_adapter::RngAdapter(&mut *rng);
+    ack.2 = serialize(&FieldWrap(Fr::random(&mut rng)))?;
+    Ok(())
+}
@@ -7,16 +7,36 @@
 // specific language governing permissions and limitations relating to use of the SAFE Network
 // Software.
 
-use crate::dev_utils::{create_ids, PeerId};
+use crate::dev_utils::{
+    create_ids, Adversary, DoubleProposal, EquivocatingInitialization, InconsistentPart, PeerId,
+    ReplayOldContext, SimConfig, SimNetwork, WrongAcks,
+};
+use crate::key_gen::abort::{AbortReason, AbortReport};
+use crate::key_gen::event::{ComplaintReason, DkgEvent};
 use crate::key_gen::mode::Mode;
-use crate::key_gen::{message::Message, Error, KeyGen};
+use crate::key_gen::outcome::Outcome;
+use crate::key_gen::params::DkgParams;
+use crate::key_gen::policy::{DkgPolicy, Quorum};
+use crate::key_gen::transcript::{Transcript, TranscriptRecorder};
+use crate::key_gen::{
+    message::{Message, MessageKind, Resend},
+    new_bivar_poly, sync_digest, AcknowledgmentFault, Error, KeyGen, PartFault, Phase, ABORT_LABEL,
+    QUALIFICATION_LABEL,
+};
 use crate::sharexorname::ShareXorName;
 use anyhow::{format_err, Result};
-use bincode::serialize;
-use blsttc::{PublicKeySet, SignatureShare};
+use bincode::{deserialize, serialize};
+use blsttc::{
+    ff::Field,
+    group::CurveAffine,
+    poly::{BivarCommitment, Poly},
+    serde_impl::{FieldWrap, SerdeSecret},
+    Fr, G1Affine, PublicKeySet, SecretKey, SecretKeyShare, SignatureShare,
+};
 use itertools::Itertools;
 use rand::{Rng, RngCore};
 use std::collections::{BTreeMap, BTreeSet};
+use std::iter;
 use xor_name::XorName;
 
 // Alter the configure of the number of nodes and the threshold.
@@ -42,6 +62,28 @@ fn create_generators<R: RngCore>(
     peer_ids: &[PeerId],
     threshold: usize,
 ) -> Result<Vec<KeyGen>> {
+    let (mut generators, mut proposals) = initialize_generators(&mut rng, peer_ids, threshold)?;
+    messaging(&mut rng, &mut generators, &mut proposals, non_responsives);
+
+    Ok(generators)
+}
+
+// Creates the `KeyGen` instances, together with their `Initialization` messages.
+fn initialize_generators<R: RngCore>(
+    rng: &mut R,
+    peer_ids: &[PeerId],
+    threshold: usize,
+) -> Result<(Vec<KeyGen>, Vec<Message>)> {
+    initialize_generators_in_mode(rng, peer_ids, threshold, Mode::Initial)
+}
+
+// Same as `initialize_generators`, for a round in the given mode.
+fn initialize_generators_in_mode<R: RngCore>(
+    rng: &mut R,
+    peer_ids: &[PeerId],
+    threshold: usize,
+    mode: Mode,
+) -> Result<(Vec<KeyGen>, Vec<Message>)> {
     // Generate individual key pairs.
     let names: BTreeSet<XorName> = peer_ids.iter().map(|peer_id| peer_id.name()).collect();
     let context = ShareXorName::from_xornames(names.clone().into_iter().collect());
@@ -52,11 +94,13 @@ fn create_generators<R: RngCore>(
     for peer_id in peer_ids.iter() {
         let key_gen = {
             let (key_gen, proposal) = match KeyGen::initialize(
+                rng,
                 peer_id.name(),
                 context.clone(),
                 threshold,
                 names.clone(),
-                Mode::Initial,
+                mode.clone(),
+                peer_id.identity(peer_ids),
             ) {
                 Ok(result) => result,
                 Err(err) => {
@@ -74,16 +118,26 @@ fn create_generators<R: RngCore>(
         generators.push(key_gen);
     }
 
-    messaging(&mut rng, &mut generators, &mut proposals, non_responsives);
-
-    Ok(generators)
+    Ok((generators, proposals))
 }
 
 fn messaging<R: RngCore>(
+    rng: &mut R,
+    generators: &mut Vec<KeyGen>,
+    proposals: &mut Vec<Message>,
+    non_responsives: BTreeSet<u64>,
+) {
+    messaging_with(rng, generators, proposals, non_responsives, Some)
+}
+
+// Same as `messaging`, but every outgoing message passes through `tamper` first, which can alter
+// or drop it.
+fn messaging_with<R: RngCore, F: FnMut(Message) -> Option<Message>>(
     mut rng: &mut R,
     generators: &mut Vec<KeyGen>,
     proposals: &mut Vec<Message>,
     non_responsives: BTreeSet<u64>,
+    mut tamper: F,
 ) {
     // Keep broadcasting the proposals among the generators till no more.
     // The proposal from non_responsive nodes shall be ignored.
@@ -95,8 +149,9 @@ fn messaging<R: RngCore>(
                 if let Ok(proposal_vec) = generator.handle_message(&mut rng, proposal.clone()) {
                     if !non_responsives.contains(&(index as u64)) {
                         proposal_vec
-                            .iter()
-                            .for_each(|prop| proposals.push(prop.clone()));
+                            .into_iter()
+                            .filter_map(&mut tamper)
+                            .for_each(|prop| proposals.push(prop));
                     }
                 }
             }
@@ -105,14 +160,940 @@ fn messaging<R: RngCore>(
 }
 
 #[test]
-fn all_nodes_being_responsive() -> Result<()> {
+fn all_nodes_being_responsive() -> Result<()> {
+    let mut rng = rand::thread_rng();
+    let (_, mut generators) = setup_generators(&mut rng, BTreeSet::new())?;
+    // With all participants responding properly, the key generating procedure shall be completed
+    // automatically. As when there is no complaint, Justification phase will be triggered directly.
+    assert!(generators
+        .iter_mut()
+        .all(|key_gen| key_gen.generate_keys().is_ok()));
+    Ok(())
+}
+
+#[test]
+fn all_nodes_agree_on_qualified_set() -> Result<()> {
+    let mut rng = rand::thread_rng();
+    let (_, generators) = setup_generators(&mut rng, BTreeSet::new())?;
+
+    let all_proposers: BTreeSet<u64> = (0..NODENUM as u64).collect();
+    for generator in generators.iter() {
+        assert_eq!(generator.agreed_qual(), Some(&all_proposers));
+    }
+    Ok(())
+}
+
+#[test]
+fn events_describe_the_progress() -> Result<()> {
+    let mut rng = rand::thread_rng();
+    let (_, mut generators) = setup_generators(&mut rng, BTreeSet::new())?;
+
+    for generator in generators.iter_mut() {
+        let pk = generator.generate_keys()?.1.public_key_set.public_key();
+        let events = generator.drain_events();
+        let phases: Vec<_> = events
+            .iter()
+            .filter_map(|event| match event {
+                DkgEvent::PhaseChanged { to, .. } => Some(*to),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            phases,
+            vec![Phase::Contribution, Phase::Complaining, Phase::Finalization]
+        );
+        for index in 0..NODENUM as u64 {
+            assert!(events.contains(&DkgEvent::PartAccepted { from: index }));
+            assert!(events.contains(&DkgEvent::AckAccepted {
+                from: index,
+                for_part: 0
+            }));
+        }
+        assert_eq!(events.last(), Some(&DkgEvent::Finalized { pk }));
+        assert!(generator.drain_events().is_empty());
+    }
+    Ok(())
+}
+
+#[test]
+fn missing_part_is_recovered_from_acknowledgments() -> Result<()> {
+    let mut rng = rand::thread_rng();
+    let peer_ids = create_ids(NODENUM);
+    let (mut generators, mut proposals) = initialize_generators(&mut rng, &peer_ids, THRESHOLD)?;
+
+    // The `Part` proposed by `proposer` to `victim` gets lost.
+    let (proposer, victim) = (1, 3);
+    messaging_with(
+        &mut rng,
+        &mut generators,
+        &mut proposals,
+        BTreeSet::new(),
+        |msg| match &msg {
+            Message::Proposal {
+                key_gen_id, part, ..
+            } if *key_gen_id == proposer && part.receiver == victim => None,
+            _ => Some(msg),
+        },
+    );
+
+    let events = generators[victim as usize].drain_events();
+    assert!(events.contains(&DkgEvent::RowRecovered { from: proposer }));
+    assert!(!events
+        .iter()
+        .any(|event| matches!(event, DkgEvent::ComplaintRaised { .. })));
+
+    // Nobody got excluded, and everyone generated the same keys without waiting for a timeout.
+    let pub_key_set = generators[0].generate_keys()?.1.public_key_set;
+    for generator in generators.iter() {
+        let (names, outcome) = generator.generate_keys()?;
+        assert_eq!(names.len(), NODENUM);
+        assert_eq!(outcome.public_key_set, pub_key_set);
+    }
+    Ok(())
+}
+
+#[test]
+fn acknowledgments_count_before_the_part_of_their_sender() -> Result<()> {
+    let mut rng = rand::thread_rng();
+    let peer_ids = create_ids(NODENUM);
+    let (mut generators, mut proposals) = initialize_generators(&mut rng, &peer_ids, THRESHOLD)?;
+
+    // Hold back every `Part`, to deliver them in our own order.
+    let mut held = Vec::new();
+    messaging_with(
+        &mut rng,
+        &mut generators,
+        &mut proposals,
+        BTreeSet::new(),
+        |msg| match msg {
+            Message::Proposal { .. } => {
+                held.push(msg);
+                None
+            }
+            _ => Some(msg),
+        },
+    );
+    let part = |proposer: u64, receiver: u64| {
+        held.iter()
+            .find(|msg| {
+                matches!(msg, Message::Proposal { key_gen_id, part, .. }
+                    if *key_gen_id == proposer && part.receiver == receiver)
+            })
+            .cloned()
+            .ok_or_else(|| format_err!("No part of {} for {}", proposer, receiver))
+    };
+
+    // Node 0 gets the acknowledgment of node 2 for the part of node 1 before the part of node 2.
+    let _ = generators[0].handle_message(&mut rng, part(1, 0)?)?;
+    let ack = generators[2]
+        .handle_message(&mut rng, part(1, 2)?)?
+        .into_iter()
+        .find(|msg| matches!(msg, Message::Acknowledgment { ack, .. } if ack.1 == 0))
+        .ok_or_else(|| format_err!("No acknowledgment for node 0"))?;
+    let values = match &ack {
+        Message::Acknowledgment { ack, .. } => ack.3.clone(),
+        other => return Err(format_err!("Unexpected message {:?}", other)),
+    };
+    let _ = generators[0].drain_events();
+    assert_eq!(generators[0].handle_message(&mut rng, ack), Ok(Vec::new()));
+
+    assert_eq!(
+        generators[0].drain_events(),
+        vec![DkgEvent::AckAccepted {
+            from: 2,
+            for_part: 1
+        }]
+    );
+    assert_eq!(generators[0].pending_messages.iter().count(), 0);
+    assert_eq!(generators[0].parts.len(), 1);
+    assert!(generators[0].parts[&1].acks.contains(&2));
+    assert_eq!(generators[0].parts[&1].enc_values, values);
+    Ok(())
+}
+
+#[test]
+fn missed_messages_are_resent_on_request() -> Result<()> {
+    let mut rng = rand::thread_rng();
+    let peer_ids = create_ids(NODENUM);
+    let (mut generators, mut proposals) = initialize_generators(&mut rng, &peer_ids, THRESHOLD)?;
+
+    // The `victim` misses the `Part` of `proposer` together with all the acknowledgments for it,
+    // so it can't rebuild its row.
+    let (proposer, victim) = (1, 3);
+    messaging_with(
+        &mut rng,
+        &mut generators,
+        &mut proposals,
+        BTreeSet::new(),
+        |msg| match &msg {
+            Message::Proposal {
+                key_gen_id, part, ..
+            } if *key_gen_id == proposer && part.receiver == victim => None,
+            Message::Acknowledgment { ack, .. } if ack.0 == proposer && ack.1 == victim => None,
+            _ => Some(msg),
+        },
+    );
+    assert_eq!(generators[victim as usize].phase(), Phase::Contribution);
+
+    // The `Part` comes first, then the acknowledgments that were buffered or lost.
+    let mut requests = generators[victim as usize].resend_requests();
+    assert_eq!(
+        requests
+            .iter()
+            .map(|msg| match msg {
+                Message::ResendRequest { what, .. } => Some(*what),
+                _ => None,
+            })
+            .collect::<Vec<_>>(),
+        vec![Some(Resend::Proposal(proposer))]
+    );
+    while !requests.is_empty() {
+        messaging(&mut rng, &mut generators, &mut requests, BTreeSet::new());
+        requests = generators[victim as usize].resend_requests();
+    }
+
+    let pub_key_set = generators[0].generate_keys()?.1.public_key_set;
+    let (names, outcome) = generators[victim as usize].generate_keys()?;
+    assert_eq!(names.len(), NODENUM);
+    assert_eq!(outcome.public_key_set, pub_key_set);
+    Ok(())
+}
+
+#[test]
+fn resend_requests_are_rate_limited() -> Result<()> {
+    let mut rng = rand::thread_rng();
+    let (_, mut generators) = setup_generators(&mut rng, BTreeSet::new())?;
+    let request = Message::ResendRequest {
+        key_gen_id: 2,
+        context: generators[1].context(),
+        what: Resend::Proposal(1),
+    };
+
+    // Requests for parts nobody proposed share the budget of the requester.
+    let unknown = Message::ResendRequest {
+        key_gen_id: 2,
+        context: generators[1].context(),
+        what: Resend::Acks(NODENUM as u64 + 1),
+    };
+
+    let budget = 3 * NODENUM;
+    let mut answers = Vec::new();
+    for count in 0..budget + 4 {
+        let msg = if count % 2 == 0 { &request } else { &unknown };
+        answers.push(generators[1].handle_message(&mut rng, msg.clone())?);
+    }
+    assert_eq!(generators[1].resends.len(), 1);
+    for (count, answer) in answers.iter().enumerate() {
+        let expected = if count < budget && count % 2 == 0 {
+            1
+        } else {
+            0
+        };
+        assert_eq!(answer.len(), expected);
+        for msg in answer {
+            assert!(
+                matches!(msg, Message::Proposal { key_gen_id: 1, part, .. } if part.receiver == 2)
+            );
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn late_member_catches_up_through_sync() -> Result<()> {
+    let mut rng = rand::thread_rng();
+    let peer_ids = create_ids(NODENUM);
+    let (mut generators, mut proposals) = initialize_generators(&mut rng, &peer_ids, THRESHOLD)?;
+
+    // The last member is offline while the others start the session.
+    let mut late = generators.pop().expect("NODENUM generators");
+    let _ = proposals.pop();
+    messaging(&mut rng, &mut generators, &mut proposals, BTreeSet::new());
+    assert!(generators
+        .iter()
+        .all(|generator| generator.phase() == Phase::Contribution));
+    assert_eq!(late.phase(), Phase::Initialization);
+
+    // A response that doesn't match its digest is rejected, as is one altered along with its
+    // digest, or attributed to another member or to a stranger.
+    let responses = generators[0].handle_message(&mut rng, late.sync_request())?;
+    let tampered = |tamper: &dyn Fn(&mut Message)| {
+        let mut response = responses[0].clone();
+        tamper(&mut response);
+        response
+    };
+    let response = tampered(&|response| {
+        if let Message::SyncResponse { messages, .. } = response {
+            let _ = messages.pop();
+        }
+    });
+    assert_eq!(
+        late.handle_message(&mut rng, response),
+        Err(Error::SyncDigestMismatch(0))
+    );
+    let response = tampered(&|response| {
+        if let Message::SyncResponse {
+            context,
+            requester,
+            phase,
+            messages,
+            digest,
+            ..
+        } = response
+        {
+            *phase = Phase::Finalization;
+            *digest = sync_digest(context, *requester, *phase, messages).expect("serialized");
+        }
+    });
+    assert_eq!(
+        late.handle_message(&mut rng, response),
+        Err(Error::InvalidSignature(0))
+    );
+    let response = tampered(&|response| {
+        if let Message::SyncResponse { key_gen_id, .. } = response {
+            *key_gen_id = 1;
+        }
+    });
+    assert_eq!(
+        late.handle_message(&mut rng, response),
+        Err(Error::InvalidSignature(1))
+    );
+    let stranger = NODENUM as u64;
+    let response = tampered(&|response| {
+        if let Message::SyncResponse { key_gen_id, .. } = response {
+            *key_gen_id = stranger;
+        }
+    });
+    assert_eq!(
+        late.handle_message(&mut rng, response),
+        Err(Error::UnknownSender(stranger))
+    );
+    assert_eq!(late.phase(), Phase::Initialization);
+
+    generators.push(late);
+    let mut requests = vec![generators[NODENUM - 1].sync_request()];
+    messaging(&mut rng, &mut generators, &mut requests, BTreeSet::new());
+
+    let pub_key_set = generators[0].generate_keys()?.1.public_key_set;
+    for generator in generators.iter() {
+        let (names, outcome) = generator.generate_keys()?;
+        assert_eq!(names.len(), NODENUM);
+        assert_eq!(outcome.public_key_set, pub_key_set);
+    }
+    Ok(())
+}
+
+#[test]
+fn member_restarted_mid_round_gets_no_share() -> Result<()> {
+    let mut rng = rand::thread_rng();
+    let peer_ids = create_ids(NODENUM);
+    let (mut generators, mut proposals) = initialize_generators(&mut rng, &peer_ids, THRESHOLD)?;
+
+    // The last member restarts after sending its part, before acknowledging the others.
+    let restarted = NODENUM - 1;
+    messaging_with(
+        &mut rng,
+        &mut generators,
+        &mut proposals,
+        BTreeSet::new(),
+        |msg| match msg {
+            Message::Acknowledgment { key_gen_id, .. } if key_gen_id == restarted as u64 => None,
+            _ => Some(msg),
+        },
+    );
+    assert_eq!(generators[0].phase(), Phase::Contribution);
+    let names: BTreeSet<XorName> = peer_ids.iter().map(|peer_id| peer_id.name()).collect();
+    generators[restarted] = KeyGen::initialize(
+        &mut rng,
+        peer_ids[restarted].name(),
+        generators[0].context(),
+        THRESHOLD,
+        names,
+        Mode::Initial,
+        peer_ids[restarted].identity(&peer_ids),
+    )?
+    .0;
+
+    // Syncing gets it into the round, but with a part other than the one it sent before.
+    let mut requests = vec![generators[restarted].sync_request()];
+    messaging(&mut rng, &mut generators, &mut requests, BTreeSet::new());
+    assert_eq!(generators[restarted].phase(), Phase::Contribution);
+    for generator in generators[..restarted].iter_mut() {
+        assert!(generator
+            .drain_events()
+            .contains(&DkgEvent::ComplaintRaised {
+                target: restarted as u64,
+                reason: ComplaintReason::Part(PartFault::MultipleParts),
+            }));
+    }
+
+    // Only the member it was sent to can tell, so the others complete the session with the part it
+    // sent before, which it can't give a share of.
+    let mut msgs = Vec::new();
+    for _ in 0..2 {
+        for generator in generators.iter_mut() {
+            if let Ok(transition) = generator.timed_phase_transition(&mut rng) {
+                msgs.extend(transition);
+            }
+        }
+        messaging(&mut rng, &mut generators, &mut msgs, BTreeSet::new());
+    }
+    let pub_key_set = generators[0].generate_keys()?.1.public_key_set;
+    for generator in generators[..restarted].iter() {
+        let (names, outcome) = generator.generate_keys()?;
+        assert_eq!(names.len(), NODENUM);
+        assert_eq!(outcome.public_key_set, pub_key_set);
+    }
+    assert!(generators[restarted].generate_keys().is_err());
+    Ok(())
+}
+
+#[test]
+fn aborts_take_effect_with_a_quorum() -> Result<()> {
+    let mut rng = rand::thread_rng();
+    let peer_ids = create_ids(NODENUM);
+    let (mut generators, mut proposals) = initialize_generators(&mut rng, &peer_ids, THRESHOLD)?;
+
+    // The last member never sends anything, so the others wait for its part.
+    let absent = NODENUM as u64 - 1;
+    let _ = proposals.pop();
+    messaging(
+        &mut rng,
+        &mut generators,
+        &mut proposals,
+        iter::once(absent).collect(),
+    );
+    assert_eq!(generators[0].phase(), Phase::Contribution);
+    let _ = generators[0].drain_events();
+
+    // With 7 members, a majority of 4 aborts blaming the same members is required.
+    let timeout = AbortReason::Timeout(Phase::Contribution);
+    let aborts = [(1, timeout), (2, AbortReason::Cancelled), (3, timeout)]
+        .iter()
+        .map(|(index, reason)| generators[*index].abort(*reason))
+        .collect::<Result<Vec<_>, _>>()?;
+    // Repeating an abort doesn't count it twice.
+    for abort in [&aborts[0], &aborts[0], &aborts[1]].iter() {
+        assert_eq!(
+            generators[0].handle_message(&mut rng, (*abort).clone()),
+            Ok(Vec::new())
+        );
+    }
+    // Nor can a member abort on behalf of another one.
+    let forged = match generators[4].abort(timeout)? {
+        Message::Abort {
+            context,
+            reason,
+            blamed,
+            signature,
+            ..
+        } => Message::Abort {
+            key_gen_id: 3,
+            context,
+            reason,
+            blamed,
+            signature,
+        },
+        other => return Err(format_err!("Unexpected message {:?}", other)),
+    };
+    assert_eq!(
+        generators[0].handle_message(&mut rng, forged),
+        Err(Error::InvalidSignature(3))
+    );
+    assert_eq!(
+        generators[0].handle_message(&mut rng, aborts[2].clone()),
+        Ok(Vec::new())
+    );
+    // An abort blaming other members doesn't agree with the others.
+    let context = generators[0].context();
+    let blamed: BTreeSet<u64> = iter::once(0).collect();
+    let disagreeing = Message::Abort {
+        key_gen_id: 5,
+        context: context.clone(),
+        reason: timeout,
+        signature: peer_ids[5].identity(&peer_ids).sign(
+            ABORT_LABEL,
+            &context,
+            5,
+            &(timeout, &blamed),
+        )?,
+        blamed,
+    };
+    assert_eq!(
+        generators[0].handle_message(&mut rng, disagreeing),
+        Ok(Vec::new())
+    );
+    assert_eq!(generators[0].abort_report(), None);
+    assert!(generators[0].drain_events().is_empty());
+    let abort = generators[4].abort(timeout)?;
+    assert_eq!(
+        generators[0].handle_message(&mut rng, abort),
+        Ok(Vec::new())
+    );
+
+    let report = AbortReport {
+        reason: timeout,
+        blamed: iter::once(peer_ids[absent as usize].name()).collect(),
+        aborting: peer_ids[1..5]
+            .iter()
+            .map(|peer_id| peer_id.name())
+            .collect(),
+    };
+    assert_eq!(generators[0].abort_report(), Some(report.clone()));
+    assert_eq!(
+        generators[0].drain_events(),
+        vec![DkgEvent::Aborted(report)]
+    );
+
+    // Blaming a member outside of the session is rejected.
+    let blamed: BTreeSet<u64> = iter::once(NODENUM as u64).collect();
+    let abort = Message::Abort {
+        key_gen_id: 4,
+        context: context.clone(),
+        reason: timeout,
+        signature: peer_ids[4].identity(&peer_ids).sign(
+            ABORT_LABEL,
+            &context,
+            4,
+            &(timeout, &blamed),
+        )?,
+        blamed,
+    };
+    assert_eq!(
+        generators[0].handle_message(&mut rng, abort),
+        Err(Error::UnknownTarget(NODENUM as u64))
+    );
+    Ok(())
+}
+
+#[test]
+fn progress_reports_missing_contributions() -> Result<()> {
+    let mut rng = rand::thread_rng();
+    let non_responsives: BTreeSet<u64> = vec![0].into_iter().collect();
+    let (peer_ids, generators) = setup_generators(&mut rng, non_responsives)?;
+
+    let progress = generators[1].progress();
+    assert_eq!(progress.phase, Phase::Contribution);
+    assert_eq!(progress.threshold, THRESHOLD);
+    assert!(!progress.parts_held.contains(&0));
+    assert_eq!(progress.parts_held.len(), NODENUM - 1);
+    // Node 0 never acknowledged any part.
+    assert!(progress
+        .ack_counts
+        .values()
+        .all(|count| *count == NODENUM - 1));
+    assert_eq!(progress.complete_parts, NODENUM - 1);
+    assert!(progress.complaints.is_empty());
+    assert_eq!(
+        progress.blockers,
+        vec![peer_ids[0].name()].into_iter().collect()
+    );
+    Ok(())
+}
+
+#[test]
+fn missing_qualification_votes_block_key_generation() -> Result<()> {
+    let mut rng = rand::thread_rng();
+    let peer_ids = create_ids(NODENUM);
+    let (mut generators, mut proposals) = initialize_generators(&mut rng, &peer_ids, THRESHOLD)?;
+
+    messaging_with(
+        &mut rng,
+        &mut generators,
+        &mut proposals,
+        BTreeSet::new(),
+        |msg| match msg {
+            Message::Qualification { .. } => None,
+            _ => Some(msg),
+        },
+    );
+
+    for generator in generators.iter() {
+        assert!(generator.is_finalized());
+        assert_eq!(generator.generate_keys().err(), Some(Error::QualNotAgreed));
+    }
+    Ok(())
+}
+
+#[test]
+fn qualified_set_mismatch_is_reported() -> Result<()> {
+    let mut rng = rand::thread_rng();
+    let peer_ids = create_ids(NODENUM);
+    let (mut generators, mut proposals) = initialize_generators(&mut rng, &peer_ids, THRESHOLD)?;
+
+    // Every node votes for a proposer nobody holds a part of.
+    let unknown_proposer = NODENUM as u64;
+    messaging_with(
+        &mut rng,
+        &mut generators,
+        &mut proposals,
+        BTreeSet::new(),
+        |msg| match msg {
+            Message::Qualification {
+                key_gen_id,
+                context,
+                mut qual,
+                ..
+            } => {
+                let _ = qual.insert(unknown_proposer);
+                let signature = peer_ids[key_gen_id as usize]
+                    .identity(&peer_ids)
+                    .sign(QUALIFICATION_LABEL, &context, key_gen_id, &qual)
+                    .ok()?;
+                Some(Message::Qualification {
+                    key_gen_id,
+                    context,
+                    qual,
+                    signature,
+                })
+            }
+            _ => Some(msg),
+        },
+    );
+
+    for generator in generators.iter() {
+        match generator.generate_keys() {
+            Err(Error::QualMismatch { agreed, local }) => {
+                assert!(agreed.contains(&unknown_proposer));
+                assert!(!local.contains(&unknown_proposer));
+            }
+            other => return Err(format_err!("Unexpected result {:?}", other)),
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn qualification_votes_must_be_signed_by_their_sender() -> Result<()> {
+    let mut rng = rand::thread_rng();
+    let (peer_ids, mut generators) = setup_generators(&mut rng, BTreeSet::new())?;
+    let context = generators[0].context();
+    let qual: BTreeSet<u64> = iter::once(NODENUM as u64).collect();
+    let vote = |signer: &PeerId, key_gen_id: u64, qual: BTreeSet<u64>| -> Result<Message> {
+        let signature =
+            signer
+                .identity(&peer_ids)
+                .sign(QUALIFICATION_LABEL, &context, key_gen_id, &qual)?;
+        Ok(Message::Qualification {
+            key_gen_id,
+            context: context.clone(),
+            qual,
+            signature,
+        })
+    };
+
+    // A member can't vote on behalf of another one, nor have its vote altered.
+    let forged = vote(&peer_ids[2], 1, qual.clone())?;
+    assert_eq!(
+        generators[0].handle_message(&mut rng, forged),
+        Err(Error::InvalidSignature(1))
+    );
+    let altered = Message::Qualification {
+        key_gen_id: 1,
+        context: context.clone(),
+        qual: BTreeSet::new(),
+        signature: peer_ids[1]
+            .identity(&peer_ids)
+            .sign(QUALIFICATION_LABEL, &context, 1, &qual)?,
+    };
+    assert_eq!(
+        generators[0].handle_message(&mut rng, altered),
+        Err(Error::InvalidSignature(1))
+    );
+    assert!(generators[0]
+        .handle_message(&mut rng, vote(&peer_ids[1], 1, qual)?)
+        .is_ok());
+    Ok(())
+}
+
+#[test]
+fn outcome_survives_storage() -> Result<()> {
+    let mut rng = rand::thread_rng();
+    let (_, generators) = setup_generators(&mut rng, BTreeSet::new())?;
+
+    let outcome = generators[0].generate_keys()?.1;
+    let stored = Outcome::from_bytes(&outcome.to_bytes()?)?;
+    assert_eq!(stored.public_key_set, outcome.public_key_set);
+    assert_eq!(stored.secret_key_share, outcome.secret_key_share);
+    assert_eq!(stored.index, outcome.index);
+    Ok(())
+}
+
+#[test]
+fn all_nodes_confirm_the_same_public_key_set() -> Result<()> {
+    let mut rng = rand::thread_rng();
+    let (_, generators) = setup_generators(&mut rng, BTreeSet::new())?;
+
+    let pub_key_set = generators[0].generate_keys()?.1.public_key_set;
+    for generator in generators.iter() {
+        let confirmed = generator
+            .confirmed_outcome()
+            .ok_or_else(|| format_err!("{:?} has no confirmed outcome", generator))?;
+        assert_eq!(confirmed.outcome.public_key_set, pub_key_set);
+        assert!(confirmed.verify());
+    }
+    Ok(())
+}
+
+#[test]
+fn missing_confirmations_leave_outcome_unconfirmed() -> Result<()> {
+    let mut rng = rand::thread_rng();
+    let peer_ids = create_ids(NODENUM);
+    let (mut generators, mut proposals) = initialize_generators(&mut rng, &peer_ids, THRESHOLD)?;
+
+    // Only `THRESHOLD - 1` nodes get their confirmation through. Even counting its own share, no
+    // node gets the `THRESHOLD + 1` shares required to combine.
+    messaging_with(
+        &mut rng,
+        &mut generators,
+        &mut proposals,
+        BTreeSet::new(),
+        |msg| match msg {
+            Message::Finalized { key_gen_id, .. } if key_gen_id + 1 >= THRESHOLD as u64 => None,
+            _ => Some(msg),
+        },
+    );
+
+    for generator in generators.iter() {
+        assert!(generator.generate_keys().is_ok());
+        assert!(generator.confirmed_outcome().is_none());
+    }
+    Ok(())
+}
+
+#[test]
+fn invalid_proof_of_possession_triggers_complaint() -> Result<()> {
+    let mut rng = rand::thread_rng();
+    let peer_ids = create_ids(NODENUM);
+    let (mut generators, mut proposals) = initialize_generators(&mut rng, &peer_ids, THRESHOLD)?;
+
+    // Node 0 signs with a key unrelated to the constant term it committed to.
+    let forged_proof = SecretKey::random().sign(b"not the constant term");
+    messaging_with(
+        &mut rng,
+        &mut generators,
+        &mut proposals,
+        BTreeSet::new(),
+        |msg| match msg {
+            Message::Proposal {
+                key_gen_id: 0,
+                context,
+                mut part,
+            } => {
+                part.proof = forged_proof.clone();
+                Some(Message::Proposal {
+                    key_gen_id: 0,
+                    context,
+                    part,
+                })
+            }
+            _ => Some(msg),
+        },
+    );
+
+    for generator in generators.iter_mut() {
+        assert!(!generator.parts.contains_key(&0));
+        assert!(generator
+            .drain_events()
+            .contains(&DkgEvent::ComplaintRaised {
+                target: 0,
+                reason: ComplaintReason::Part(PartFault::ProofOfPossession),
+            }));
+        let complaints = generator.timed_phase_transition(&mut rng)?;
+        assert!(complaints
+            .iter()
+            .any(|msg| matches!(msg, Message::Complaint { target: 0, .. })));
+    }
+    Ok(())
+}
+
+#[test]
+fn refresh_parts_with_a_constant_term_trigger_complaint() -> Result<()> {
+    let mut rng = rand::thread_rng();
+    let peer_ids = create_ids(NODENUM);
+    let (mut generators, mut proposals) =
+        initialize_generators_in_mode(&mut rng, &peer_ids, THRESHOLD, Mode::Refresh)?;
+
+    // Node 0 proposes a part with a constant term, which would change the group key. Its proof of
+    // possession is valid, but proves nothing in a refresh.
+    let initial_part = new_bivar_poly(&mut rng, THRESHOLD, &Mode::Initial);
+    let forged = generators[0].create_proposals(&initial_part)?;
+    messaging_with(
+        &mut rng,
+        &mut generators,
+        &mut proposals,
+        BTreeSet::new(),
+        |msg| match msg {
+            Message::Proposal {
+                key_gen_id: 0,
+                part,
+                ..
+            } => forged
+                .iter()
+                .find(|forged| match forged {
+                    Message::Proposal { part: forged, .. } => forged.receiver == part.receiver,
+                    _ => false,
+                })
+                .cloned(),
+            _ => Some(msg),
+        },
+    );
+
+    for generator in generators.iter_mut() {
+        assert!(!generator.parts.contains_key(&0));
+        assert!(generator
+            .drain_events()
+            .contains(&DkgEvent::ComplaintRaised {
+                target: 0,
+                reason: ComplaintReason::Part(PartFault::Mode),
+            }));
+    }
+    Ok(())
+}
+
+#[test]
+fn complaints_against_unknown_members_are_rejected() -> Result<()> {
+    let mut rng = rand::thread_rng();
+    let peer_ids = create_ids(NODENUM);
+    let names: BTreeSet<XorName> = peer_ids.iter().map(|peer_id| peer_id.name()).collect();
+    let context = ShareXorName::from_xornames(names.iter().cloned().collect());
+    let mut key_gen = KeyGen::initialize_for_test(
+        peer_ids[0].name(),
+        0,
+        context.clone(),
+        names,
+        THRESHOLD,
+        Phase::Complaining,
+    );
+
+    let unknown = NODENUM as u64;
+    let complaint = |key_gen_id, target| Message::Complaint {
+        key_gen_id,
+        target,
+        context: context.clone(),
+        msg: Vec::new(),
+    };
+    assert_eq!(
+        key_gen.handle_message(&mut rng, complaint(1, unknown)),
+        Err(Error::UnknownTarget(unknown))
+    );
+    assert_eq!(
+        key_gen.handle_message(&mut rng, complaint(unknown, 1)),
+        Err(Error::UnknownSender(unknown))
+    );
+    Ok(())
+}
+
+#[test]
+fn repeated_complaints_are_counted_once() -> Result<()> {
+    let mut rng = rand::thread_rng();
+    let peer_ids = create_ids(NODENUM);
+    let names: BTreeSet<XorName> = peer_ids.iter().map(|peer_id| peer_id.name()).collect();
+    let context = ShareXorName::from_xornames(names.iter().cloned().collect());
+    let mut key_gen = KeyGen::initialize_for_test(
+        peer_ids[0].name(),
+        0,
+        context.clone(),
+        names,
+        THRESHOLD,
+        Phase::Complaining,
+    );
+
+    // Two members complain against the third one, one of them twice, e.g. as the network
+    // duplicated its complaint. That is one complaint short of excluding the target.
+    let target = 3;
+    for sender in [1, 1, 2].iter() {
+        let complaint = Message::Complaint {
+            key_gen_id: *sender,
+            target,
+            context: context.clone(),
+            msg: Vec::new(),
+        };
+        let _ = key_gen.handle_message(&mut rng, complaint)?;
+    }
+    let target_name = peer_ids[target as usize].name();
+    assert_eq!(key_gen.progress().complaints.get(&target_name), Some(&2));
+
+    let _ = key_gen.timed_phase_transition(&mut rng)?;
+    assert!(!key_gen
+        .drain_events()
+        .contains(&DkgEvent::MemberExcluded(target_name)));
+    Ok(())
+}
+
+#[test]
+fn pending_messages_are_bounded_per_sender() -> Result<()> {
     let mut rng = rand::thread_rng();
-    let (_, mut generators) = setup_generators(&mut rng, BTreeSet::new())?;
-    // With all participants responding properly, the key generating procedure shall be completed
-    // automatically. As when there is no complaint, Justification phase will be triggered directly.
-    assert!(generators
-        .iter_mut()
-        .all(|key_gen| key_gen.generate_keys().is_some()));
+    let peer_ids = create_ids(NODENUM);
+    let names: BTreeSet<XorName> = peer_ids.iter().map(|peer_id| peer_id.name()).collect();
+    let context = ShareXorName::from_xornames(names.iter().cloned().collect());
+    let mut key_gen = KeyGen::initialize_for_test(
+        peer_ids[0].name(),
+        0,
+        context.clone(),
+        names,
+        THRESHOLD,
+        Phase::Contribution,
+    );
+
+    let complaint = |key_gen_id| Message::Complaint {
+        key_gen_id,
+        target: 2,
+        context: context.clone(),
+        msg: Vec::new(),
+    };
+    // A member spamming complaints ahead of the Complaining phase only fills its own buffer.
+    let capacity = NODENUM * (NODENUM + 1);
+    for _ in 0..capacity + 10 {
+        assert_eq!(
+            key_gen.handle_message(&mut rng, complaint(1)),
+            Ok(Vec::new())
+        );
+    }
+    assert_eq!(
+        key_gen.handle_message(&mut rng, complaint(3)),
+        Ok(Vec::new())
+    );
+
+    let progress = key_gen.progress();
+    assert_eq!(progress.dropped_messages, 10);
+    assert_eq!(
+        progress.pending_messages.get(&MessageKind::Complaint),
+        Some(&(capacity + 1))
+    );
+    Ok(())
+}
+
+#[test]
+fn pending_messages_of_unknown_senders_are_dropped() -> Result<()> {
+    let mut rng = rand::thread_rng();
+    let peer_ids = create_ids(NODENUM);
+    let names: BTreeSet<XorName> = peer_ids.iter().map(|peer_id| peer_id.name()).collect();
+    let context = ShareXorName::from_xornames(names.iter().cloned().collect());
+    let mut key_gen = KeyGen::initialize_for_test(
+        peer_ids[0].name(),
+        0,
+        context.clone(),
+        names,
+        THRESHOLD,
+        Phase::Contribution,
+    );
+
+    // Rotating the claimed sender doesn't get a spammer a fresh buffer.
+    let spam = 100;
+    for key_gen_id in NODENUM as u64..(NODENUM + spam) as u64 {
+        let complaint = Message::Complaint {
+            key_gen_id,
+            target: 2,
+            context: context.clone(),
+            msg: Vec::new(),
+        };
+        assert_eq!(key_gen.handle_message(&mut rng, complaint), Ok(Vec::new()));
+    }
+
+    let progress = key_gen.progress();
+    assert!(progress.pending_messages.is_empty());
+    assert_eq!(progress.dropped_messages, spam);
     Ok(())
 }
 
@@ -172,7 +1153,7 @@ fn having_max_unresponsive_nodes_still_work() -> Result<()> {
         for (index, key_gen) in generators.iter_mut().enumerate() {
             // // // FIX
             if !non_responsives.contains(&(index as u64)) {
-                let outcome = if let Some(outcome) = key_gen.generate_keys() {
+                let outcome = if let Ok(outcome) = key_gen.generate_keys() {
                     outcome.1
                 } else {
                     return Err(format_err!(
@@ -192,7 +1173,7 @@ fn having_max_unresponsive_nodes_still_work() -> Result<()> {
                     assert!(!key_gen.names().contains(&peer_ids[*idx as usize].name()))
                 });
             } else {
-                assert!(key_gen.generate_keys().is_none());
+                assert!(key_gen.generate_keys().is_err());
             };
         }
 
@@ -206,6 +1187,215 @@ fn having_max_unresponsive_nodes_still_work() -> Result<()> {
     Ok(())
 }
 
+// Runs a round in `mode` in which the first member is unresponsive, and returns the commitments
+// proposed once it got excluded by the others.
+fn commitments_after_exclusion(mode: Mode) -> Result<Vec<BivarCommitment>> {
+    let mut rng = rand::thread_rng();
+    let peer_ids = create_ids(NODENUM);
+    let (mut generators, mut proposals) =
+        initialize_generators_in_mode(&mut rng, &peer_ids, THRESHOLD, mode)?;
+    let non_responsives: BTreeSet<u64> = vec![0].into_iter().collect();
+    messaging(
+        &mut rng,
+        &mut generators,
+        &mut proposals,
+        non_responsives.clone(),
+    );
+
+    // The first transition raises the complaints, the second one excludes the member.
+    for _ in 0..2 {
+        for generator in generators.iter_mut().skip(1) {
+            proposals.extend(generator.timed_phase_transition(&mut rng)?);
+        }
+        if generators[1].phase() == Phase::Commitment {
+            break;
+        }
+        messaging(
+            &mut rng,
+            &mut generators,
+            &mut proposals,
+            non_responsives.clone(),
+        );
+    }
+    assert_eq!(generators[1].phase(), Phase::Commitment);
+
+    Ok(proposals
+        .into_iter()
+        .filter_map(|msg| match msg {
+            Message::Proposal { part, .. } => Some(part.commitment),
+            _ => None,
+        })
+        .collect())
+}
+
+#[test]
+fn commitment_rerun_keeps_the_mode() -> Result<()> {
+    let zero = G1Affine::one().mul(Fr::zero());
+
+    let commitments = commitments_after_exclusion(Mode::Initial)?;
+    assert!(!commitments.is_empty());
+    for commitment in commitments {
+        assert_ne!(commitment.evaluate(0, 0), zero);
+    }
+
+    // A refresh keeps the group key: the new constant terms add up to zero.
+    for commitment in commitments_after_exclusion(Mode::Refresh)? {
+        assert_eq!(commitment.evaluate(0, 0), zero);
+    }
+
+    let recovered = 2;
+    for commitment in commitments_after_exclusion(Mode::Recovery(recovered))? {
+        assert_eq!(commitment.evaluate(recovered + 1, 0), zero);
+        assert_ne!(commitment.evaluate(0, 0), zero);
+    }
+    Ok(())
+}
+
+// Runs a round in `mode` among `peer_ids` in which the first member is unresponsive, and returns
+// the outcomes of the others once they completed it without that member.
+fn outcomes_after_exclusion(peer_ids: &[PeerId], mode: Mode) -> Result<Vec<Outcome>> {
+    let mut rng = rand::thread_rng();
+    let (mut generators, mut proposals) =
+        initialize_generators_in_mode(&mut rng, peer_ids, THRESHOLD, mode)?;
+    let non_responsives: BTreeSet<u64> = vec![0].into_iter().collect();
+    messaging(
+        &mut rng,
+        &mut generators,
+        &mut proposals,
+        non_responsives.clone(),
+    );
+
+    // The first transition raises the complaints, the second one excludes the member and reruns
+    // the commitment among the others.
+    for _ in 0..2 {
+        for generator in generators.iter_mut().skip(1) {
+            proposals.extend(generator.timed_phase_transition(&mut rng)?);
+        }
+        messaging(
+            &mut rng,
+            &mut generators,
+            &mut proposals,
+            non_responsives.clone(),
+        );
+    }
+
+    let mut outcomes = Vec::new();
+    for generator in generators.iter().skip(1) {
+        assert!(!generator.names().contains(&peer_ids[0].name()));
+        outcomes.push(generator.generate_keys()?.1);
+    }
+    Ok(outcomes)
+}
+
+// Returns the field element of a secret key share.
+fn share_value(share: &SecretKeyShare) -> Result<Fr> {
+    let bytes = serialize(&SerdeSecret(share.clone()))?;
+    Ok(deserialize::<FieldWrap<Fr>>(&bytes)?.into_inner())
+}
+
+#[test]
+fn refresh_and_recovery_complete_after_a_complaint() -> Result<()> {
+    let mut rng = rand::thread_rng();
+    let peer_ids = create_ids(NODENUM);
+    let mut original = BTreeMap::new();
+    let mut pub_key_set = None;
+    for generator in create_generators(&mut rng, BTreeSet::new(), &peer_ids, THRESHOLD)? {
+        let outcome = generator.generate_keys()?.1;
+        let _ = original.insert(
+            outcome.index as u64,
+            share_value(&outcome.secret_key_share)?,
+        );
+        pub_key_set = Some(outcome.public_key_set);
+    }
+    let pub_key_set = pub_key_set.expect("members");
+
+    // Adding the shares of a refresh gives new shares of the same group key.
+    let msg = "Test message!";
+    let zero_key = PublicKeySet::from(Poly::zero().commitment()).public_key();
+    let mut sig_shares = BTreeMap::new();
+    for outcome in outcomes_after_exclusion(&peer_ids, Mode::Refresh)? {
+        assert_eq!(outcome.public_key_set.public_key(), zero_key);
+        let index = outcome.index as u64;
+        let mut refreshed = original[&index];
+        refreshed.add_assign(&share_value(&outcome.secret_key_share)?);
+        assert_ne!(refreshed, original[&index]);
+        let _ = sig_shares.insert(index, SecretKeyShare::from_mut(&mut refreshed).sign(msg));
+    }
+    assert_eq!(sig_shares.len(), NODENUM - 1);
+    let sig = pub_key_set.combine_signatures(sig_shares.iter())?;
+    assert!(pub_key_set.public_key().verify(&sig, msg));
+
+    // The shares of a recovery mask those of the others, which together give back the share of
+    // the member that lost it.
+    let lost = ShareXorName::from_xornames(peer_ids.iter().map(|id| id.name()).collect())
+        .get_share(peer_ids[0].name())
+        .expect("a member");
+    let mut masked = BTreeMap::new();
+    for outcome in outcomes_after_exclusion(&peer_ids, Mode::Recovery(lost))? {
+        let index = outcome.index as u64;
+        let mut value = original[&index];
+        value.add_assign(&share_value(&outcome.secret_key_share)?);
+        let _ = masked.insert(index + 1, value);
+    }
+    assert!(!masked.contains_key(&(lost + 1)));
+    let recovered = Poly::interpolate(masked.iter().take(THRESHOLD + 1)).evaluate(lost + 1);
+    assert_eq!(recovered, original[&lost]);
+    Ok(())
+}
+
+#[test]
+fn exclusion_moves_members_to_the_same_derived_context() -> Result<()> {
+    let mut rng = rand::thread_rng();
+    let peer_ids = create_ids(NODENUM);
+    let (mut generators, mut proposals) = initialize_generators(&mut rng, &peer_ids, THRESHOLD)?;
+    let non_responsives: BTreeSet<u64> = vec![0].into_iter().collect();
+    messaging(
+        &mut rng,
+        &mut generators,
+        &mut proposals,
+        non_responsives.clone(),
+    );
+    let initial_context = generators[1].context();
+
+    for generator in generators.iter_mut().skip(1) {
+        proposals.extend(generator.timed_phase_transition(&mut rng)?);
+    }
+    messaging(
+        &mut rng,
+        &mut generators,
+        &mut proposals,
+        non_responsives.clone(),
+    );
+
+    // Members exclude the unresponsive one after the other, so the proposals of those already in
+    // the derived context reach the others while they are still complaining.
+    for index in 1..NODENUM {
+        proposals.extend(generators[index].timed_phase_transition(&mut rng)?);
+        messaging(
+            &mut rng,
+            &mut generators,
+            &mut proposals,
+            non_responsives.clone(),
+        );
+    }
+
+    let excluded = peer_ids[0].name();
+    let context = generators[1].context();
+    assert!(context.get_share(excluded).is_none());
+    assert_ne!(context.get_keygenid(), initial_context.get_keygenid());
+    let mut public_key_sets = BTreeSet::new();
+    for generator in generators.iter().skip(1) {
+        assert_eq!(generator.context(), context);
+        assert_eq!(
+            context.get_share(generator.our_id),
+            initial_context.get_share(generator.our_id)
+        );
+        let _ = public_key_sets.insert(generator.generate_keys()?.1.public_key_set);
+    }
+    assert_eq!(public_key_sets.len(), 1);
+    Ok(())
+}
+
 #[test]
 fn having_min_unresponsive_nodes_cause_block() -> Result<()> {
     let mut rng = rand::thread_rng();
@@ -276,7 +1466,7 @@ fn threshold_signature() -> Result<()> {
     for (idx, generator) in generators.iter().enumerate() {
         // // // // FIX
         assert!(generator.is_ready());
-        let outcome = if let Some(outcome) = generator.generate_keys() {
+        let outcome = if let Ok(outcome) = generator.generate_keys() {
             outcome.1
         } else {
             return Err(format_err!(
@@ -358,7 +1548,7 @@ fn threshold_encrypt() -> Result<()> {
     for (idx, generator) in generators.iter().enumerate() {
         // // // FIX
         assert!(generator.is_ready());
-        let outcome = if let Some(outcome) = generator.generate_keys() {
+        let outcome = if let Ok(outcome) = generator.generate_keys() {
             outcome.1
         } else {
             return Err(format_err!(
@@ -430,7 +1620,564 @@ fn network_churning() -> Result<()> {
 
         assert!(generators
             .iter_mut()
-            .all(|key_gen| key_gen.generate_keys().is_some()));
+            .all(|key_gen| key_gen.generate_keys().is_ok()));
+    }
+    Ok(())
+}
+
+#[test]
+fn initialization_waits_for_the_policy_quorum() -> Result<()> {
+    let mut rng = rand::thread_rng();
+    let peer_ids = create_ids(NODENUM);
+    let names: BTreeSet<XorName> = peer_ids.iter().map(|peer_id| peer_id.name()).collect();
+    let context = ShareXorName::from_xornames(names.iter().cloned().collect());
+    let policy = DkgPolicy {
+        initialization: Quorum::AtLeast(THRESHOLD + 1),
+        ..DkgPolicy::default()
+    };
+
+    let mut initializations = Vec::new();
+    for peer_id in peer_ids.iter() {
+        let params = DkgParams::builder(peer_id.name(), context.clone(), THRESHOLD)
+            .policy(policy)
+            .identity(peer_id.identity(&peer_ids))
+            .build()?;
+        initializations.push(KeyGen::with_params(&mut rng, params)?);
+    }
+    let (mut key_gen, _) = initializations.remove(0);
+    for (index, (_, msg)) in initializations.into_iter().enumerate() {
+        let _ = key_gen.handle_message(&mut rng, msg)?;
+        let expected = if index < THRESHOLD {
+            Phase::Initialization
+        } else {
+            Phase::Contribution
+        };
+        assert_eq!(key_gen.phase(), expected);
+    }
+    Ok(())
+}
+
+#[test]
+fn replayed_transcript_reproduces_the_session() -> Result<()> {
+    let mut rng = rand::thread_rng();
+    let peer_ids = create_ids(NODENUM);
+    let names: BTreeSet<XorName> = peer_ids.iter().map(|peer_id| peer_id.name()).collect();
+    let context = ShareXorName::from_xornames(names.iter().cloned().collect());
+
+    let mut recorders = Vec::new();
+    let mut messages = Vec::new();
+    for peer_id in peer_ids.iter() {
+        let params = DkgParams::builder(peer_id.name(), context.clone(), THRESHOLD)
+            .identity(peer_id.identity(&peer_ids))
+            .build()?;
+        let (recorder, msg) = TranscriptRecorder::with_params(&mut rng, params)?;
+        recorders.push(recorder);
+        messages.push(msg);
+    }
+    while !messages.is_empty() {
+        for msg in std::mem::take(&mut messages) {
+            for recorder in recorders.iter_mut() {
+                if let Ok(new_messages) = recorder.handle_message(&mut rng, msg.clone()) {
+                    messages.extend(new_messages);
+                }
+            }
+        }
+    }
+
+    let (_, recorded) = recorders[0].key_gen().generate_keys()?;
+    let transcript = Transcript::from_bytes(&recorders[0].transcript().to_bytes()?)?;
+    assert_eq!(&transcript, recorders[0].transcript());
+
+    let replay = transcript.replay()?;
+    assert_eq!(replay.steps.len(), transcript.entries().len());
+    assert!(replay.steps.iter().all(|step| !step.rng_exhausted));
+    assert_eq!(replay.key_gen.phase(), Phase::Finalization);
+    let (_, replayed) = replay.outcome()?;
+    assert_eq!(replayed.public_key_set, recorded.public_key_set);
+    assert_eq!(replayed.secret_key_share, recorded.secret_key_share);
+    Ok(())
+}
+
+#[test]
+fn sim_network_tolerates_reordering_and_duplicates() -> Result<()> {
+    let config = SimConfig {
+        seed: 7,
+        min_delay: 1,
+        max_delay: 50,
+        duplicate_probability: 0.2,
+        ..SimConfig::default()
+    };
+    let mut network = SimNetwork::new(config, NODENUM, THRESHOLD, Mode::Initial)?;
+    assert!(network.run_until(100_000));
+    assert!(network.all_finalized());
+
+    let public_keys: BTreeSet<_> = (0..NODENUM)
+        .map(|node| {
+            network
+                .key_gen(node)
+                .generate_keys()
+                .map(|(_, outcome)| outcome.public_key_set.public_key())
+        })
+        .collect::<Result<_, _>>()?;
+    assert_eq!(public_keys.len(), 1);
+    Ok(())
+}
+
+#[test]
+fn sim_network_is_deterministic() -> Result<()> {
+    let config = SimConfig {
+        seed: 42,
+        min_delay: 1,
+        max_delay: 20,
+        ..SimConfig::default()
+    };
+    let mut public_keys = Vec::new();
+    for _ in 0..2 {
+        let mut network = SimNetwork::new(config.clone(), NODENUM, THRESHOLD, Mode::Initial)?;
+        assert!(network.run_until(100_000));
+        let (_, outcome) = network.key_gen(0).generate_keys()?;
+        public_keys.push((network.now(), outcome.public_key_set.public_key()));
+    }
+    assert_eq!(public_keys[0], public_keys[1]);
+    Ok(())
+}
+
+// Runs a session in which the last member behaves as `adversary`, returning the network once idle.
+fn run_with_adversary(adversary: Box<dyn Adversary>) -> Result<SimNetwork> {
+    let mut network = SimNetwork::new(SimConfig::default(), NODENUM, THRESHOLD, Mode::Initial)?;
+    network.set_adversary(NODENUM - 1, adversary);
+    assert!(network.run_until(1_000_000));
+    Ok(network)
+}
+
+// Asserts that all honest members finalized with the same confirmed public key, and returns
+// the events of each of them.
+fn assert_honest_members_agree(network: &mut SimNetwork) -> Result<Vec<Vec<DkgEvent>>> {
+    let mut public_keys = BTreeSet::new();
+    let mut events = Vec::new();
+    for node in 0..NODENUM - 1 {
+        let key_gen = network.key_gen_mut(node);
+        let confirmed = key_gen
+            .confirmed_outcome()
+            .ok_or_else(|| format_err!("node #{} did not confirm the outcome", node))?;
+        let _ = public_keys.insert(confirmed.outcome.public_key_set.public_key());
+        events.push(key_gen.drain_events());
+    }
+    assert_eq!(public_keys.len(), 1);
+    Ok(events)
+}
+
+fn complained(events: &[DkgEvent], target: u64, reason: ComplaintReason) -> bool {
+    events.contains(&DkgEvent::ComplaintRaised { target, reason })
+}
+
+#[test]
+fn inconsistent_part_is_detected_by_its_receiver() -> Result<()> {
+    let attacker = NODENUM as u64 - 1;
+    let mut network = run_with_adversary(Box::new(InconsistentPart { victim: 0 }))?;
+    let events = assert_honest_members_agree(&mut network)?;
+    assert!(complained(
+        &events[0],
+        attacker,
+        ComplaintReason::Part(PartFault::RowAcknowledgment)
+    ));
+    Ok(())
+}
+
+#[test]
+fn double_proposal_is_detected() -> Result<()> {
+    let attacker = NODENUM as u64 - 1;
+    let mut network = run_with_adversary(Box::new(DoubleProposal))?;
+    let events = assert_honest_members_agree(&mut network)?;
+    assert!(events.iter().all(|events| complained(
+        events,
+        attacker,
+        ComplaintReason::Part(PartFault::MultipleParts)
+    )));
+    Ok(())
+}
+
+#[test]
+fn wrong_acks_are_not_counted() -> Result<()> {
+    let attacker = NODENUM as u64 - 1;
+    let mut network = run_with_adversary(Box::new(WrongAcks))?;
+    let events = assert_honest_members_agree(&mut network)?;
+    assert!(events.iter().all(|events| complained(
+        events,
+        attacker,
+        ComplaintReason::Acknowledgment(AcknowledgmentFault::ValueAcknowledgment)
+    )));
+    // The attacker's acknowledgments were rejected, so no part counts it among its acks.
+    let progress = network.key_gen(0).progress();
+    assert!(progress
+        .ack_counts
+        .values()
+        .all(|count| *count == NODENUM - 1));
+    Ok(())
+}
+
+#[test]
+fn false_complaints_exclude_nobody() -> Result<()> {
+    let mut rng = rand::thread_rng();
+    let peer_ids = create_ids(NODENUM);
+    let (mut generators, mut msgs) = initialize_generators(&mut rng, &peer_ids, THRESHOLD)?;
+
+    // Two acknowledgments of a part get lost, leaving it one short of complete, so that the
+    // members time out into the Complaining phase without anyone to complain about.
+    messaging_with(
+        &mut rng,
+        &mut generators,
+        &mut msgs,
+        BTreeSet::new(),
+        |msg| match &msg {
+            Message::Acknowledgment {
+                key_gen_id, ack, ..
+            } if ack.0 == 2 && (*key_gen_id == 0 || *key_gen_id == 1) => None,
+            _ => Some(msg),
+        },
+    );
+    for generator in generators.iter_mut() {
+        msgs.extend(generator.timed_phase_transition(&mut rng)?);
+        assert_eq!(generator.phase(), Phase::Complaining);
+    }
+
+    // The last member complains against the first one, which did nothing wrong.
+    let target = peer_ids[0].name();
+    msgs.push(Message::Complaint {
+        key_gen_id: NODENUM as u64 - 1,
+        target: 0,
+        context: generators[0].context(),
+        msg: b"Not contributed".to_vec(),
+    });
+    messaging(&mut rng, &mut generators, &mut msgs, BTreeSet::new());
+    for generator in generators.iter() {
+        assert_eq!(generator.progress().complaints.get(&target), Some(&1));
+    }
+
+    // The members run another round among all of them.
+    for generator in generators.iter_mut() {
+        msgs.extend(generator.timed_phase_transition(&mut rng)?);
+    }
+    messaging(&mut rng, &mut generators, &mut msgs, BTreeSet::new());
+    let pub_key_set = generators[0].generate_keys()?.1.public_key_set;
+    for generator in generators.iter_mut() {
+        assert!(!generator
+            .drain_events()
+            .iter()
+            .any(|event| matches!(event, DkgEvent::MemberExcluded(_))));
+        let (names, outcome) = generator.generate_keys()?;
+        assert_eq!(names.len(), NODENUM);
+        assert_eq!(outcome.public_key_set, pub_key_set);
+    }
+    Ok(())
+}
+
+#[test]
+fn equivocating_initialization_is_outvoted() -> Result<()> {
+    let mut network = run_with_adversary(Box::new(EquivocatingInitialization))?;
+    let _ = assert_honest_members_agree(&mut network)?;
+    Ok(())
+}
+
+#[test]
+fn conflicting_initialization_parameters_are_reported() -> Result<()> {
+    let mut rng = rand::thread_rng();
+    let peer_ids = create_ids(NODENUM);
+    let (mut generators, mut initializations) =
+        initialize_generators(&mut rng, &peer_ids, THRESHOLD)?;
+
+    // One member starts the session in another mode, another one in another context.
+    let mut conflicting = BTreeSet::new();
+    if let Message::Initialization {
+        key_gen_id, mode, ..
+    } = &mut initializations[1]
+    {
+        *mode = Mode::Refresh;
+        let _ = conflicting.insert(*key_gen_id);
+    }
+    if let Message::Initialization {
+        key_gen_id,
+        context,
+        ..
+    } = &mut initializations[2]
+    {
+        context.set_keygenid([1; 32]);
+        let _ = conflicting.insert(*key_gen_id);
+    }
+
+    let generator = &mut generators[0];
+    for initialization in initializations {
+        let foreign = matches!(
+            &initialization,
+            Message::Initialization { context, .. } if *context != generator.context()
+        );
+        let result = generator.handle_message(&mut rng, initialization);
+        if foreign {
+            assert!(matches!(result, Err(Error::ContextMismatch { .. })));
+        }
+    }
+    assert_eq!(generator.phase(), Phase::Contribution);
+    assert_eq!(generator.mode(), Mode::Initial);
+    assert_eq!(generator.initialization_conflicts(), conflicting);
+    Ok(())
+}
+
+#[test]
+fn replayed_old_context_messages_are_rejected() -> Result<()> {
+    // The same members ran a previous session under another keygen id.
+    let config = SimConfig {
+        keygenid: [1; 32],
+        ..SimConfig::default()
+    };
+    let mut old_network = SimNetwork::new(config, NODENUM, THRESHOLD, Mode::Initial)?;
+    assert!(old_network.run_until(1_000_000));
+    let old_context = old_network.key_gen(0).context();
+    let old_messages: Vec<Message> = old_network
+        .sent_messages()
+        .iter()
+        .map(|(_, msg)| msg.clone())
+        .collect();
+
+    let mut network = SimNetwork::new(SimConfig::default(), NODENUM, THRESHOLD, Mode::Initial)?;
+    let context = network.key_gen(0).context();
+    assert_eq!(context.xornames, old_context.xornames);
+    assert_ne!(context.keygenid, old_context.keygenid);
+
+    // None of the messages of the previous session is accepted, whatever its kind.
+    let mut rng = rand::thread_rng();
+    for msg in old_messages.iter() {
+        assert!(matches!(
+            network.key_gen_mut(0).handle_message(&mut rng, msg.clone()),
+            Err(Error::ContextMismatch { .. })
+        ));
     }
+
+    // Nor do they get in the way of the session when replayed along with its messages.
+    network.set_adversary(NODENUM - 1, Box::new(ReplayOldContext::new(old_messages)));
+    assert!(network.run_until(1_000_000));
+    let _ = assert_honest_members_agree(&mut network)?;
+    assert!(network
+        .errors()
+        .iter()
+        .any(|error| matches!(error.error, Error::ContextMismatch { .. })));
     Ok(())
 }
+
+#[cfg(feature = "metrics")]
+mod instrumentation {
+    use super::*;
+    use metrics::{
+        Counter, CounterFn, Gauge, Histogram, HistogramFn, Key, KeyName, Recorder, SharedString,
+        Unit,
+    };
+    use std::cell::RefCell;
+    use std::sync::{Arc, Once};
+
+    thread_local! {
+        // The values recorded by the current thread, by name and labels, as other tests may run
+        // at the same time.
+        static RECORDED: RefCell<BTreeMap<String, Vec<f64>>> =
+            const { RefCell::new(BTreeMap::new()) };
+    }
+
+    struct ThreadRecorder;
+
+    struct Handle(String);
+
+    impl Handle {
+        fn push(&self, value: f64) {
+            RECORDED.with(|recorded| {
+                recorded
+                    .borrow_mut()
+                    .entry(self.0.clone())
+                    .or_default()
+                    .push(value)
+            });
+        }
+    }
+
+    impl CounterFn for Handle {
+        fn increment(&self, value: u64) {
+            self.push(value as f64);
+        }
+
+        fn absolute(&self, value: u64) {
+            self.push(value as f64);
+        }
+    }
+
+    impl HistogramFn for Handle {
+        fn record(&self, value: f64) {
+            self.push(value);
+        }
+    }
+
+    impl Recorder for ThreadRecorder {
+        fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+        fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+        fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+        fn register_counter(&self, key: &Key) -> Counter {
+            Counter::from_arc(Arc::new(Handle(key_string(key))))
+        }
+
+        fn register_gauge(&self, _: &Key) -> Gauge {
+            Gauge::noop()
+        }
+
+        fn register_histogram(&self, key: &Key) -> Histogram {
+            Histogram::from_arc(Arc::new(Handle(key_string(key))))
+        }
+    }
+
+    // Formats `key` as `name{label=value,...}`.
+    fn key_string(key: &Key) -> String {
+        let labels = key
+            .labels()
+            .map(|label| format!("{}={}", label.key(), label.value()))
+            .join(",");
+        format!("{}{{{}}}", key.name(), labels)
+    }
+
+    // Runs `f` and returns what the current thread recorded meanwhile.
+    fn record<F: FnOnce() -> Result<()>>(f: F) -> Result<BTreeMap<String, Vec<f64>>> {
+        static INSTALL: Once = Once::new();
+        INSTALL.call_once(|| {
+            metrics::set_boxed_recorder(Box::new(ThreadRecorder)).expect("no other recorder")
+        });
+        RECORDED.with(|recorded| recorded.borrow_mut().clear());
+        f()?;
+        Ok(RECORDED.with(|recorded| recorded.take()))
+    }
+
+    fn total(recorded: &BTreeMap<String, Vec<f64>>, key: &str) -> f64 {
+        recorded
+            .get(key)
+            .map(|values| values.iter().sum())
+            .unwrap_or(0.0)
+    }
+
+    #[test]
+    fn a_round_records_its_counters_and_histograms() -> Result<()> {
+        let recorded = record(|| {
+            let mut rng = rand::thread_rng();
+            let (_, generators) = setup_generators(&mut rng, BTreeSet::new())?;
+            assert!(generators.iter().all(KeyGen::is_finalized));
+            Ok(())
+        })?;
+        let members = NODENUM as f64;
+
+        assert_eq!(
+            total(&recorded, "bls_dkg_rounds_started_total{mode=initial}"),
+            members
+        );
+        assert_eq!(
+            total(
+                &recorded,
+                "bls_dkg_rounds_finished_total{mode=initial,result=finalized}"
+            ),
+            members
+        );
+
+        // Every member went through the phases up to the finalization once.
+        for phase in &[
+            Phase::Initialization,
+            Phase::Contribution,
+            Phase::Complaining,
+        ] {
+            let key = format!("bls_dkg_phase_duration_seconds{{phase={:?}}}", phase);
+            let durations = &recorded[&key];
+            assert_eq!(durations.len(), NODENUM);
+            assert!(durations.iter().all(|duration| *duration >= 0.0));
+        }
+        assert!(!recorded.contains_key("bls_dkg_phase_duration_seconds{phase=Finalization}"));
+
+        // Each message is sent once and received by every member, the sender included.
+        for kind in &[
+            "Initialization",
+            "Proposal",
+            "Acknowledgment",
+            "Qualification",
+        ] {
+            let sent = total(
+                &recorded,
+                &format!("bls_dkg_messages_sent_total{{kind={}}}", kind),
+            );
+            let received = total(
+                &recorded,
+                &format!("bls_dkg_messages_received_total{{kind={}}}", kind),
+            );
+            assert!(sent > 0.0, "no {} sent", kind);
+            assert_eq!(received, members * sent, "{} received", kind);
+
+            let bytes_sent = total(
+                &recorded,
+                &format!("bls_dkg_message_bytes_sent_total{{kind={}}}", kind),
+            );
+            let bytes_received = total(
+                &recorded,
+                &format!("bls_dkg_message_bytes_received_total{{kind={}}}", kind),
+            );
+            assert!(bytes_sent > sent, "{} bytes sent", kind);
+            assert_eq!(
+                bytes_received,
+                members * bytes_sent,
+                "{} bytes received",
+                kind
+            );
+        }
+
+        // Nobody misbehaved.
+        assert!(!recorded
+            .keys()
+            .any(|key| key.starts_with("bls_dkg_complaints_raised_total")
+                || key.starts_with("bls_dkg_members_excluded_total")));
+        Ok(())
+    }
+
+    #[test]
+    fn a_failed_round_is_recorded_once() -> Result<()> {
+        let recorded = record(|| {
+            let mut rng = rand::thread_rng();
+            let non_responsives: BTreeSet<u64> = (0..(NODENUM - THRESHOLD) as u64).collect();
+            let (_, mut generators) = setup_generators(&mut rng, non_responsives.clone())?;
+            let mut complaints = Vec::new();
+            for generator in generators.iter_mut() {
+                complaints.extend(generator.timed_phase_transition(&mut rng)?);
+            }
+            messaging(&mut rng, &mut generators, &mut complaints, non_responsives);
+
+            // The session keeps timing out in the Complaining phase, then gets aborted.
+            let aborts = generators[NODENUM - THRESHOLD..]
+                .iter()
+                .map(|generator| generator.abort(AbortReason::TooManyNonVoters))
+                .collect::<Result<Vec<_>, _>>()?;
+            let generator = &mut generators[NODENUM - 1];
+            for _ in 0..3 {
+                assert!(matches!(
+                    generator.timed_phase_transition(&mut rng),
+                    Err(Error::TooManyNonVoters(_))
+                ));
+            }
+            for abort in aborts {
+                let _ = generator.handle_message(&mut rng, abort)?;
+            }
+            assert!(generator.abort_report().is_some());
+            Ok(())
+        })?;
+
+        assert_eq!(
+            total(
+                &recorded,
+                "bls_dkg_rounds_finished_total{mode=initial,result=too_many_non_voters}"
+            ),
+            1.0
+        );
+        assert!(!recorded
+            .keys()
+            .any(|key| key.contains("result=aborted") || key.contains("result=finalized")));
+        Ok(())
+    }
+}
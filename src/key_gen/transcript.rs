@@ -0,0 +1,284 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// https://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+use super::event::DkgEvent;
+use super::message::Message;
+use super::outcome::Outcome;
+use super::params::DkgParams;
+use super::{Error, KeyGen, Phase};
+use bincode::{deserialize, serialize};
+use rand::RngCore;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use xor_name::XorName;
+
+/// A log of every input applied to a `KeyGen`, together with the random bytes drawn while
+/// handling it, so that the session can be replayed offline.
+///
+/// # Security
+///
+/// **A transcript is as sensitive as the secret-key share it produces.** The recorded random
+/// bytes are those the `KeyGen` derived its encryption keys and its bivariate polynomial from,
+/// and the parameters hold the secret key of our [`Identity`](super::identity::Identity). Anyone
+/// holding a transcript can therefore decrypt the rows sent to us, rebuild our secret-key share
+/// and sign votes on our behalf. Store and share transcripts only where the share itself may be
+/// kept, and delete them along with it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Transcript {
+    params: DkgParams,
+    /// Random bytes drawn while creating the `KeyGen`.
+    init_rng: Vec<u8>,
+    entries: Vec<TranscriptEntry>,
+}
+
+/// An input applied to a `KeyGen`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TranscriptEntry {
+    /// A call to `KeyGen::handle_message`.
+    Message { msg: Box<Message>, rng: Vec<u8> },
+    /// A call to `KeyGen::timed_phase_transition`.
+    TimedTransition { rng: Vec<u8> },
+}
+
+impl Transcript {
+    pub fn params(&self) -> &DkgParams {
+        &self.params
+    }
+
+    pub fn entries(&self) -> &[TranscriptEntry] {
+        &self.entries
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        Ok(serialize(self)?)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Transcript, Error> {
+        Ok(deserialize(bytes)?)
+    }
+
+    /// Feeds the transcript into a fresh `KeyGen`, reporting the result of each entry.
+    pub fn replay(&self) -> Result<Replay, Error> {
+        let mut rng = ReplayRng::new(&self.init_rng);
+        let (mut key_gen, _) = KeyGen::with_params(&mut rng, self.params.clone())?;
+        let mut steps = Vec::new();
+        for entry in self.entries.iter() {
+            let (result, rng_exhausted) = match entry {
+                TranscriptEntry::Message { msg, rng } => {
+                    let mut rng = ReplayRng::new(rng);
+                    let result = key_gen.handle_message(&mut rng, (**msg).clone());
+                    (result, rng.exhausted)
+                }
+                TranscriptEntry::TimedTransition { rng } => {
+                    let mut rng = ReplayRng::new(rng);
+                    let result = key_gen.timed_phase_transition(&mut rng);
+                    (result, rng.exhausted)
+                }
+            };
+            steps.push(ReplayStep {
+                entry: entry.clone(),
+                phase: key_gen.phase(),
+                result,
+                events: key_gen.drain_events(),
+                rng_exhausted,
+            });
+        }
+        Ok(Replay { key_gen, steps })
+    }
+}
+
+/// The result of replaying one `TranscriptEntry`.
+#[derive(Debug)]
+pub struct ReplayStep {
+    pub entry: TranscriptEntry,
+    /// The phase after the entry was applied.
+    pub phase: Phase,
+    /// The messages the entry produced, or the error it caused.
+    pub result: Result<Vec<Message>, Error>,
+    pub events: Vec<DkgEvent>,
+    /// Whether more random bytes were drawn than were recorded, in which case the replay has
+    /// diverged from the recorded session, e.g. because the transcript comes from another version
+    /// of this crate.
+    pub rng_exhausted: bool,
+}
+
+/// The outcome of `Transcript::replay`.
+pub struct Replay {
+    pub key_gen: KeyGen,
+    pub steps: Vec<ReplayStep>,
+}
+
+impl Replay {
+    /// The keys generated by the replayed session.
+    pub fn outcome(&self) -> Result<(BTreeSet<XorName>, Outcome), Error> {
+        self.key_gen.generate_keys()
+    }
+}
+
+/// Wraps a `KeyGen`, recording every input applied to it into a `Transcript`.
+pub struct TranscriptRecorder {
+    key_gen: KeyGen,
+    transcript: Transcript,
+}
+
+impl TranscriptRecorder {
+    /// Creates a new recorded `KeyGen`, together with the `Initial` message that should be
+    /// multicast to all nodes.
+    pub fn with_params<R: RngCore>(
+        rng: &mut R,
+        params: DkgParams,
+    ) -> Result<(TranscriptRecorder, Message), Error> {
+        let mut init_rng = Vec::new();
+        let (key_gen, msg) =
+            KeyGen::with_params(&mut RecordingRng::new(rng, &mut init_rng), params.clone())?;
+        let transcript = Transcript {
+            params,
+            init_rng,
+            entries: Vec::new(),
+        };
+        Ok((
+            TranscriptRecorder {
+                key_gen,
+                transcript,
+            },
+            msg,
+        ))
+    }
+
+    /// See `KeyGen::handle_message`.
+    pub fn handle_message<R: RngCore>(
+        &mut self,
+        rng: &mut R,
+        msg: Message,
+    ) -> Result<Vec<Message>, Error> {
+        let mut drawn = Vec::new();
+        let result = self
+            .key_gen
+            .handle_message(&mut RecordingRng::new(rng, &mut drawn), msg.clone());
+        self.transcript.entries.push(TranscriptEntry::Message {
+            msg: Box::new(msg),
+            rng: drawn,
+        });
+        result
+    }
+
+    /// See `KeyGen::timed_phase_transition`.
+    pub fn timed_phase_transition<R: RngCore>(
+        &mut self,
+        rng: &mut R,
+    ) -> Result<Vec<Message>, Error> {
+        let mut drawn = Vec::new();
+        let result = self
+            .key_gen
+            .timed_phase_transition(&mut RecordingRng::new(rng, &mut drawn));
+        self.transcript
+            .entries
+            .push(TranscriptEntry::TimedTransition { rng: drawn });
+        result
+    }
+
+    /// See `KeyGen::drain_events`.
+    pub fn drain_events(&mut self) -> Vec<DkgEvent> {
+        self.key_gen.drain_events()
+    }
+
+    pub fn key_gen(&self) -> &KeyGen {
+        &self.key_gen
+    }
+
+    pub fn transcript(&self) -> &Transcript {
+        &self.transcript
+    }
+
+    pub fn into_transcript(self) -> Transcript {
+        self.transcript
+    }
+}
+
+// Forwards to the wrapped rng, appending every byte it produces to `drawn`.
+struct RecordingRng<'a, R: RngCore + ?Sized> {
+    rng: &'a mut R,
+    drawn: &'a mut Vec<u8>,
+}
+
+impl<'a, R: RngCore + ?Sized> RecordingRng<'a, R> {
+    fn new(rng: &'a mut R, drawn: &'a mut Vec<u8>) -> Self {
+        RecordingRng { rng, drawn }
+    }
+}
+
+impl<'a, R: RngCore + ?Sized> RngCore for RecordingRng<'a, R> {
+    fn next_u32(&mut self) -> u32 {
+        let value = self.rng.next_u32();
+        self.drawn.extend_from_slice(&value.to_le_bytes());
+        value
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let value = self.rng.next_u64();
+        self.drawn.extend_from_slice(&value.to_le_bytes());
+        value
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.rng.fill_bytes(dest);
+        self.drawn.extend_from_slice(dest);
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.rng.try_fill_bytes(dest)?;
+        self.drawn.extend_from_slice(dest);
+        Ok(())
+    }
+}
+
+// Plays back the bytes recorded by a `RecordingRng`, then zeros once they run out.
+struct ReplayRng<'a> {
+    tape: &'a [u8],
+    exhausted: bool,
+}
+
+impl<'a> ReplayRng<'a> {
+    fn new(tape: &'a [u8]) -> Self {
+        ReplayRng {
+            tape,
+            exhausted: false,
+        }
+    }
+}
+
+impl<'a> RngCore for ReplayRng<'a> {
+    fn next_u32(&mut self) -> u32 {
+        let mut bytes = [0; 4];
+        self.fill_bytes(&mut bytes);
+        u32::from_le_bytes(bytes)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut bytes = [0; 8];
+        self.fill_bytes(&mut bytes);
+        u64::from_le_bytes(bytes)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        let len = dest.len().min(self.tape.len());
+        let (head, tail) = self.tape.split_at(len);
+        dest[..len].copy_from_slice(head);
+        if len < dest.len() {
+            self.exhausted = true;
+            dest[len..].iter_mut().for_each(|byte| *byte = 0);
+        }
+        self.tape = tail;
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
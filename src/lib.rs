@@ -21,5 +21,8 @@ extern crate log;
 pub mod key_gen;
 pub use key_gen::*;
 
-#[cfg(test)]
-mod dev_utils;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod dev_utils;
+
+#[cfg(feature = "async")]
+pub mod session;
@@ -0,0 +1,440 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// https://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+//! Drives a `KeyGen` to completion over an asynchronous transport, available with the `async`
+//! feature.
+
+mod clock;
+mod orchestrator;
+mod tcp;
+mod transport;
+
+pub use self::clock::{Clock, TokioClock};
+pub use self::orchestrator::{DkgAttempt, DkgOrchestrator};
+pub use self::tcp::TcpTransport;
+pub use self::transport::{ChannelTransport, DkgTransport, TransportError};
+
+use crate::key_gen::{
+    abort::{AbortReason, AbortReport},
+    message::Message,
+    outcome::Outcome,
+    params::DkgParams,
+    Error, KeyGen, Phase,
+};
+use rand::{rngs::StdRng, RngCore, SeedableRng};
+use std::collections::{BTreeSet, VecDeque};
+use std::fmt::{self, Display, Formatter};
+use std::time::{Duration, Instant};
+use xor_name::XorName;
+
+/// The parameters of a session run by `run_dkg`.
+#[derive(Clone, Debug)]
+pub struct SessionParams {
+    pub dkg: DkgParams,
+    /// How long to wait in the `Contribution` and `Complaining` phases before calling
+    /// `KeyGen::timed_phase_transition`.
+    pub phase_timeout: Duration,
+    /// How long to wait for messages before asking for the missing ones again, see
+    /// `KeyGen::resend_requests` and `KeyGen::sync_request`.
+    pub resend_interval: Duration,
+    /// How long the whole session may take. After that we abort, and wait for one more phase
+    /// timeout for the aborts of the others, so that the members agree on whom to blame.
+    pub session_timeout: Duration,
+}
+
+impl SessionParams {
+    /// Parameters with a phase timeout of 30 seconds, a resend interval of 10 seconds and a
+    /// session timeout of 5 minutes.
+    pub fn new(dkg: DkgParams) -> Self {
+        SessionParams {
+            dkg,
+            phase_timeout: Duration::from_secs(30),
+            resend_interval: Duration::from_secs(10),
+            session_timeout: Duration::from_secs(300),
+        }
+    }
+}
+
+/// Why a session failed.
+#[non_exhaustive]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum FailureReason {
+    /// The session did not complete in time, and not enough members aborted it with us.
+    Timeout,
+    /// No more messages can arrive.
+    TransportClosed,
+    Transport(TransportError),
+    KeyGen(Error),
+    /// A quorum of members aborted the session, see `KeyGen::abort_report`.
+    Aborted(AbortReport),
+}
+
+/// A failed session, with the members we were waiting for.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DkgFailure {
+    pub reason: FailureReason,
+    /// The phase we were stuck in.
+    pub phase: Phase,
+    /// See `KeyGen::possible_blockers`.
+    pub possible_blockers: BTreeSet<XorName>,
+}
+
+impl Display for DkgFailure {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "DKG failed in phase {:?} ({:?}), possibly blocked by {:?}",
+            self.phase, self.reason, self.possible_blockers
+        )
+    }
+}
+
+impl std::error::Error for DkgFailure {}
+
+/// Runs a session over `transport` until our keys are generated, drawing randomness from the
+/// operating system.
+pub async fn run_dkg<T: DkgTransport, C: Clock>(
+    transport: T,
+    params: SessionParams,
+    clock: C,
+) -> Result<Outcome, DkgFailure> {
+    run_dkg_with_rng(transport, params, clock, StdRng::from_entropy()).await
+}
+
+/// Same as `run_dkg`, with the given rng.
+pub async fn run_dkg_with_rng<T: DkgTransport, C: Clock, R: RngCore + Send>(
+    mut transport: T,
+    params: SessionParams,
+    clock: C,
+    mut rng: R,
+) -> Result<Outcome, DkgFailure> {
+    let session_deadline = clock.now() + params.session_timeout;
+    let (mut key_gen, initialization) =
+        KeyGen::with_params(&mut rng, params.dkg).map_err(|error| DkgFailure {
+            reason: FailureReason::KeyGen(error),
+            phase: Phase::Initialization,
+            possible_blockers: BTreeSet::new(),
+        })?;
+    let fail = |key_gen: &KeyGen, reason| DkgFailure {
+        reason,
+        phase: key_gen.phase(),
+        possible_blockers: key_gen.possible_blockers(),
+    };
+
+    let mut outbox = VecDeque::from(vec![initialization]);
+    let mut phase = key_gen.phase();
+    let mut phase_deadline = clock.now() + params.phase_timeout;
+    let mut resend_deadline = clock.now() + params.resend_interval;
+    // Once we give up, we wait for the aborts of the others for one more phase timeout, and fail
+    // with the reason we gave up for if they don't agree on one.
+    let mut grace: Option<(Instant, FailureReason)> = None;
+    loop {
+        // Our own messages are handled by us as well as by everyone else.
+        while let Some(msg) = outbox.pop_front() {
+            transport
+                .broadcast(msg.clone())
+                .await
+                .map_err(|error| fail(&key_gen, FailureReason::Transport(error)))?;
+            handle_message(&mut key_gen, &mut rng, msg, &mut outbox);
+        }
+        if let Ok((_, outcome)) = key_gen.generate_keys() {
+            return Ok(outcome);
+        }
+        if let Some(report) = key_gen.abort_report() {
+            return Err(fail(&key_gen, FailureReason::Aborted(report)));
+        }
+
+        if key_gen.phase() != phase {
+            phase = key_gen.phase();
+            phase_deadline = clock.now() + params.phase_timeout;
+            resend_deadline = clock.now() + params.resend_interval;
+        }
+        let timed = phase == Phase::Contribution || phase == Phase::Complaining;
+        let deadline = if let Some((grace_deadline, _)) = &grace {
+            *grace_deadline
+        } else if timed {
+            phase_deadline.min(session_deadline)
+        } else {
+            session_deadline
+        };
+
+        tokio::select! {
+            msg = transport.receive() => match msg {
+                Some((_, msg)) => handle_message(&mut key_gen, &mut rng, msg, &mut outbox),
+                None => return Err(fail(&key_gen, FailureReason::TransportClosed)),
+            },
+            _ = clock.sleep_until(deadline.min(resend_deadline)) => {
+                if let Some((grace_deadline, reason)) = grace.take() {
+                    if clock.now() >= grace_deadline {
+                        return Err(fail(&key_gen, reason));
+                    }
+                    grace = Some((grace_deadline, reason));
+                    resend_deadline = grace_deadline;
+                    continue;
+                }
+                if clock.now() >= session_deadline {
+                    let abort = AbortReason::Timeout(key_gen.phase());
+                    give_up(&key_gen, abort, &mut outbox);
+                    grace = Some((clock.now() + params.phase_timeout, FailureReason::Timeout));
+                    resend_deadline = clock.now() + params.phase_timeout;
+                    continue;
+                }
+                if clock.now() < deadline {
+                    resend_deadline = clock.now() + params.resend_interval;
+                    // Still waiting for the session to start, we may have joined it late.
+                    if phase == Phase::Initialization {
+                        outbox.push_back(key_gen.sync_request());
+                    } else {
+                        outbox.extend(key_gen.resend_requests());
+                    }
+                    continue;
+                }
+                phase_deadline = clock.now() + params.phase_timeout;
+                match key_gen.timed_phase_transition(&mut rng) {
+                    Ok(msgs) => outbox.extend(msgs),
+                    Err(Error::UnexpectedPhase { .. }) => {}
+                    Err(error @ Error::TooManyNonVoters(_)) => {
+                        give_up(&key_gen, AbortReason::TooManyNonVoters, &mut outbox);
+                        let reason = FailureReason::KeyGen(error);
+                        grace = Some((clock.now() + params.phase_timeout, reason));
+                        resend_deadline = clock.now() + params.phase_timeout;
+                    }
+                    Err(error) => return Err(fail(&key_gen, FailureReason::KeyGen(error))),
+                }
+            }
+        }
+    }
+}
+
+// Messages that fail to be handled are dropped: they are faults of their sender, not ours.
+fn handle_message<R: RngCore>(
+    key_gen: &mut KeyGen,
+    rng: &mut R,
+    msg: Message,
+    outbox: &mut VecDeque<Message>,
+) {
+    match key_gen.handle_message(rng, msg) {
+        Ok(msgs) => outbox.extend(msgs),
+        Err(error) => debug!("{:?} dropped a message: {:?}", key_gen, error),
+    }
+}
+
+// Queues our abort, to be handled by us and the others alike, so that all of us can agree on
+// whom to blame. Failing to sign it doesn't change the outcome of our session.
+fn give_up(key_gen: &KeyGen, reason: AbortReason, outbox: &mut VecDeque<Message>) {
+    match key_gen.abort(reason) {
+        Ok(abort) => outbox.push_back(abort),
+        Err(error) => debug!("{:?} failed to sign an abort: {:?}", key_gen, error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dev_utils::{create_ids, PeerId};
+    use crate::key_gen::sharexorname::ShareXorName;
+
+    fn session_params(peer_ids: &[PeerId], our_id: XorName) -> SessionParams {
+        let context = ShareXorName::from_xornames(peer_ids.iter().map(|id| id.name()).collect());
+        let peer_id = peer_ids
+            .iter()
+            .find(|id| id.name() == our_id)
+            .expect("a member");
+        let dkg = DkgParams::builder(our_id, context, 3)
+            .identity(peer_id.identity(peer_ids))
+            .build()
+            .expect("valid params");
+        SessionParams::new(dkg)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn all_members_complete_over_channels() {
+        let peer_ids = create_ids(5);
+        let names: BTreeSet<XorName> = peer_ids.iter().map(|id| id.name()).collect();
+        let handles: Vec<_> = ChannelTransport::network(&names)
+            .into_iter()
+            .map(|(our_id, transport)| {
+                let params = session_params(&peer_ids, our_id);
+                tokio::spawn(run_dkg(transport, params, TokioClock))
+            })
+            .collect();
+
+        let mut public_keys = BTreeSet::new();
+        for handle in handles {
+            let outcome = handle.await.expect("no panic").expect("completed");
+            let _ = public_keys.insert(outcome.public_key_set.public_key());
+        }
+        assert_eq!(public_keys.len(), 1);
+    }
+
+    // Drops the first broadcast of each message matching `lost`, as a lossy network would.
+    struct Lossy<T> {
+        inner: T,
+        lost: fn(&Message) -> bool,
+        dropped: BTreeSet<Message>,
+    }
+
+    #[async_trait::async_trait]
+    impl<T: DkgTransport> DkgTransport for Lossy<T> {
+        async fn send_to(&mut self, peer: XorName, msg: Message) -> Result<(), TransportError> {
+            self.inner.send_to(peer, msg).await
+        }
+
+        async fn broadcast(&mut self, msg: Message) -> Result<(), TransportError> {
+            if (self.lost)(&msg) && self.dropped.insert(msg.clone()) {
+                return Ok(());
+            }
+            self.inner.broadcast(msg).await
+        }
+
+        async fn receive(&mut self) -> Option<(XorName, Message)> {
+            self.inner.receive().await
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn lost_messages_are_resent_before_the_phase_timeout() {
+        let peer_ids = create_ids(5);
+        let names: BTreeSet<XorName> = peer_ids.iter().map(|id| id.name()).collect();
+        // The parts proposed by the member of share 1 get lost, so nobody can complete.
+        fn lost(msg: &Message) -> bool {
+            matches!(msg, Message::Proposal { key_gen_id: 1, .. })
+        }
+
+        let started = tokio::time::Instant::now();
+        let handles: Vec<_> = ChannelTransport::network(&names)
+            .into_iter()
+            .map(|(our_id, inner)| {
+                let transport = Lossy {
+                    inner,
+                    lost,
+                    dropped: BTreeSet::new(),
+                };
+                tokio::spawn(run_dkg(
+                    transport,
+                    session_params(&peer_ids, our_id),
+                    TokioClock,
+                ))
+            })
+            .collect();
+
+        let mut public_keys = BTreeSet::new();
+        for handle in handles {
+            let outcome = handle.await.expect("no panic").expect("completed");
+            let _ = public_keys.insert(outcome.public_key_set.public_key());
+        }
+        assert_eq!(public_keys.len(), 1);
+        assert!(started.elapsed() < Duration::from_secs(30));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn late_member_joins_before_the_phase_timeout() {
+        let peer_ids = create_ids(5);
+        let names: BTreeSet<XorName> = peer_ids.iter().map(|id| id.name()).collect();
+        let mut transports = ChannelTransport::network(&names);
+        let late_id = *names.iter().next_back().expect("five names");
+        let late_transport = transports.remove(&late_id).expect("a transport per name");
+
+        let mut handles: Vec<_> = transports
+            .into_iter()
+            .map(|(our_id, transport)| {
+                tokio::spawn(run_dkg(
+                    transport,
+                    session_params(&peer_ids, our_id),
+                    TokioClock,
+                ))
+            })
+            .collect();
+        // The others start the session in the meantime, and their messages to us get lost.
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        let mut late_transport = late_transport;
+        while let Ok(Some(_)) =
+            tokio::time::timeout(Duration::from_millis(1), late_transport.receive()).await
+        {}
+        handles.push(tokio::spawn(run_dkg(
+            late_transport,
+            session_params(&peer_ids, late_id),
+            TokioClock,
+        )));
+
+        let mut public_keys = BTreeSet::new();
+        for handle in handles {
+            let outcome = handle.await.expect("no panic").expect("completed");
+            let _ = public_keys.insert(outcome.public_key_set.public_key());
+        }
+        assert_eq!(public_keys.len(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn times_out_without_enough_members() {
+        let peer_ids = create_ids(5);
+        let names: BTreeSet<XorName> = peer_ids.iter().map(|id| id.name()).collect();
+        let mut transports = ChannelTransport::network(&names);
+        let our_id = *names.iter().next().expect("five names");
+        let transport = transports.remove(&our_id).expect("a transport per name");
+
+        // The other members never start, but keep their transports open.
+        let failure = run_dkg(transport, session_params(&peer_ids, our_id), TokioClock)
+            .await
+            .expect_err("cannot complete alone");
+        assert_eq!(failure.reason, FailureReason::Timeout);
+        assert_eq!(failure.phase, Phase::Initialization);
+        assert_eq!(
+            failure.possible_blockers,
+            names
+                .iter()
+                .copied()
+                .filter(|name| *name != our_id)
+                .collect()
+        );
+        drop(transports);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn aborts_of_a_quorum_stop_the_others() {
+        let peer_ids = create_ids(5);
+        let names: BTreeSet<XorName> = peer_ids.iter().map(|id| id.name()).collect();
+        let mut transports = ChannelTransport::network(&names);
+        let absent_id = *names.iter().next_back().expect("five names");
+        let _absent = transports.remove(&absent_id).expect("a transport per name");
+        let patient_id = *names.iter().next().expect("five names");
+        let patient = transports
+            .remove(&patient_id)
+            .expect("a transport per name");
+
+        // Three members give up long before the last one, while still waiting for the part of the
+        // absent member.
+        let impatient: Vec<_> = transports
+            .into_iter()
+            .map(|(our_id, transport)| {
+                let mut params = session_params(&peer_ids, our_id);
+                params.session_timeout = Duration::from_secs(10);
+                tokio::spawn(run_dkg(transport, params, TokioClock))
+            })
+            .collect();
+        let started = tokio::time::Instant::now();
+        let failure = run_dkg(patient, session_params(&peer_ids, patient_id), TokioClock)
+            .await
+            .expect_err("aborted");
+
+        let report = match failure.reason {
+            FailureReason::Aborted(report) => report,
+            reason => panic!("unexpected failure {:?}", reason),
+        };
+        assert_eq!(report.reason, AbortReason::Timeout(Phase::Contribution));
+        assert!(report.blamed.contains(&absent_id));
+        assert_eq!(report.aborting.len(), 3);
+        assert!(started.elapsed() < Duration::from_secs(30));
+        // Waiting for each other's aborts, the impatient members agree with the patient one.
+        for handle in impatient {
+            let failure = handle.await.expect("no panic").expect_err("aborted");
+            assert_eq!(failure.reason, FailureReason::Aborted(report.clone()));
+        }
+    }
+}
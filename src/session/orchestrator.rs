@@ -0,0 +1,306 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// https://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+use super::{run_dkg_with_rng, Clock, DkgFailure, DkgTransport, FailureReason, SessionParams};
+use crate::key_gen::{outcome::Outcome, params::DkgParams, sharexorname::ShareXorName, Error};
+use rand::{rngs::StdRng, RngCore, SeedableRng};
+use std::collections::BTreeSet;
+use xor_name::XorName;
+
+/// Runs sessions until one completes, excluding the members blamed for each failed session from
+/// the next one.
+///
+/// Only members the others agreed on are excluded, i.e. those blamed by the `AbortReport` of a
+/// quorum, see `KeyGen::abort_report`. After any other failure, e.g. a plain timeout or
+/// `Error::TooManyNonVoters`, the blockers seen by each member may differ, so the next session
+/// runs among the same members under a fresh keygen id. Every member must use the same settings
+/// to derive the same context for the next session. The surviving members keep their shares, see
+/// `ShareXorName::to_new_xornames`.
+#[derive(Clone, Debug)]
+pub struct DkgOrchestrator {
+    params: SessionParams,
+    min_members: usize,
+    max_attempts: usize,
+    history: Vec<DkgAttempt>,
+}
+
+/// A session run by a `DkgOrchestrator`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DkgAttempt {
+    /// The context of the session.
+    pub context: ShareXorName,
+    /// The members excluded since the previous session.
+    pub excluded: BTreeSet<XorName>,
+    /// Why the session failed, or `None` if it completed.
+    pub failure: Option<DkgFailure>,
+}
+
+impl DkgOrchestrator {
+    /// An orchestrator running up to 3 sessions with `params`, for as long as more members than
+    /// the threshold are left.
+    pub fn new(params: SessionParams) -> Self {
+        let min_members = params.dkg.threshold() + 1;
+        DkgOrchestrator {
+            params,
+            min_members,
+            max_attempts: 3,
+            history: Vec::new(),
+        }
+    }
+
+    /// Sets the fewest members to run a session with. The threshold must stay below the number
+    /// of members, so a lower value has no effect.
+    pub fn min_members(mut self, min_members: usize) -> Self {
+        self.min_members = min_members;
+        self
+    }
+
+    /// Sets how many sessions to run at most.
+    pub fn max_attempts(mut self, max_attempts: usize) -> Self {
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    /// The sessions run so far, in order.
+    pub fn history(&self) -> &[DkgAttempt] {
+        &self.history
+    }
+
+    /// Runs sessions over `transport` until one completes, drawing randomness from the operating
+    /// system. The history of a previous run is cleared.
+    pub async fn run<T: DkgTransport, C: Clock + Clone>(
+        &mut self,
+        transport: T,
+        clock: C,
+    ) -> Result<Outcome, DkgFailure> {
+        self.run_with_rng(transport, clock, StdRng::from_entropy())
+            .await
+    }
+
+    /// Same as `run`, with the given rng.
+    ///
+    /// Returns the failure of the last session once `max_attempts` sessions failed. When no
+    /// further session can be run, e.g. as too few members are left or as we got blamed
+    /// ourselves, the failure carries the reason as a `FailureReason::KeyGen` error instead.
+    pub async fn run_with_rng<T: DkgTransport, C: Clock + Clone, R: RngCore + Send>(
+        &mut self,
+        mut transport: T,
+        clock: C,
+        mut rng: R,
+    ) -> Result<Outcome, DkgFailure> {
+        self.history.clear();
+        let mut params = self.params.clone();
+        let mut excluded = BTreeSet::new();
+        loop {
+            let context = params.dkg.context().clone();
+            let result =
+                run_dkg_with_rng(&mut transport, params.clone(), clock.clone(), &mut rng).await;
+            self.history.push(DkgAttempt {
+                context,
+                excluded,
+                failure: result.as_ref().err().cloned(),
+            });
+            let failure = match result {
+                Ok(outcome) => return Ok(outcome),
+                Err(failure) => failure,
+            };
+            if self.history.len() >= self.max_attempts {
+                return Err(failure);
+            }
+
+            excluded = blamed(&failure);
+            params.dkg = self
+                .next_params(&params.dkg, &excluded)
+                .map_err(|error| DkgFailure {
+                    reason: FailureReason::KeyGen(error),
+                    ..failure
+                })?;
+        }
+    }
+
+    // Returns the parameters of the session following the one run with `dkg`, among the members
+    // left after excluding `excluded`.
+    fn next_params(
+        &self,
+        dkg: &DkgParams,
+        excluded: &BTreeSet<XorName>,
+    ) -> Result<DkgParams, Error> {
+        if excluded.contains(&dkg.our_id()) {
+            return Err(Error::EvictedSelf);
+        }
+        let names: BTreeSet<XorName> = dkg.names().difference(excluded).copied().collect();
+        if names.len() < self.min_members {
+            return Err(Error::CommitteeTooSmall {
+                n: names.len(),
+                min: self.min_members,
+            });
+        }
+
+        let mut context = dkg.context().clone();
+        context.to_new_xornames(names.iter().copied().collect());
+        // Derived from the previous session and from the number of sessions run so far, so that
+        // it changes even when nobody got excluded.
+        let mut content = dkg.context().get_keygenid().to_vec();
+        content.extend_from_slice(&(self.history.len() as u64).to_be_bytes());
+        for name in excluded {
+            content.extend_from_slice(&name.0);
+        }
+        context.set_keygenid(XorName::from_content(&content).0);
+
+        DkgParams::builder(dkg.our_id(), context, dkg.threshold())
+            .mode(dkg.mode().clone())
+            .policy(*dkg.policy())
+            .identity(dkg.identity().clone())
+            .build()
+    }
+}
+
+// Returns the members to exclude after `failure`: only those the members agreed on, as our own
+// blockers and non-voters may differ from those of the others.
+fn blamed(failure: &DkgFailure) -> BTreeSet<XorName> {
+    match &failure.reason {
+        FailureReason::Aborted(report) => report.blamed.clone(),
+        _ => BTreeSet::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dev_utils::{create_ids, PeerId};
+    use crate::key_gen::abort::{AbortReason, AbortReport};
+    use crate::key_gen::Phase;
+    use crate::session::{ChannelTransport, TokioClock};
+    use std::time::Duration;
+
+    fn orchestrator(peer_ids: &[PeerId], our_id: XorName) -> DkgOrchestrator {
+        let context = ShareXorName::from_xornames(peer_ids.iter().map(|id| id.name()).collect());
+        let peer_id = peer_ids
+            .iter()
+            .find(|id| id.name() == our_id)
+            .expect("a member");
+        let dkg = DkgParams::builder(our_id, context, 3)
+            .identity(peer_id.identity(peer_ids))
+            .build()
+            .expect("valid params");
+        let mut params = SessionParams::new(dkg);
+        // Too short for the members to exclude an unresponsive one on their own.
+        params.session_timeout = Duration::from_secs(20);
+        DkgOrchestrator::new(params)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retries_without_the_blockers() {
+        let peer_ids = create_ids(5);
+        let names: BTreeSet<XorName> = peer_ids.iter().map(|id| id.name()).collect();
+        let mut transports = ChannelTransport::network(&names);
+        let absent_id = *names.iter().next_back().expect("five names");
+        let _absent = transports.remove(&absent_id).expect("a transport per name");
+
+        let handles: Vec<_> = transports
+            .into_iter()
+            .map(|(our_id, transport)| {
+                let mut orchestrator = orchestrator(&peer_ids, our_id);
+                tokio::spawn(async move {
+                    let result = orchestrator.run(transport, TokioClock).await;
+                    (result, orchestrator.history().to_vec())
+                })
+            })
+            .collect();
+
+        let mut public_keys = BTreeSet::new();
+        for handle in handles {
+            let (result, history) = handle.await.expect("no panic");
+            let _ = public_keys.insert(result.expect("completed").public_key_set.public_key());
+
+            assert_eq!(history.len(), 2);
+            assert!(history[0].failure.is_some());
+            assert_eq!(history[1].failure, None);
+            assert_eq!(history[1].excluded, std::iter::once(absent_id).collect());
+            assert_ne!(history[1].context.keygenid, history[0].context.keygenid);
+            for name in history[1].context.xornames.iter() {
+                assert_eq!(
+                    history[1].context.get_share(*name),
+                    history[0].context.get_share(*name)
+                );
+            }
+        }
+        assert_eq!(public_keys.len(), 1);
+    }
+
+    #[test]
+    fn only_agreed_blame_excludes_members() {
+        let peer_ids = create_ids(5);
+        let names: Vec<XorName> = peer_ids.iter().map(|id| id.name()).collect();
+        let failure = |reason, blocker: XorName| DkgFailure {
+            reason,
+            phase: Phase::Contribution,
+            possible_blockers: std::iter::once(blocker).collect(),
+        };
+        let next_context = |index: usize, failure: &DkgFailure| {
+            let orchestrator = orchestrator(&peer_ids, names[index]);
+            let dkg = &orchestrator.params.dkg;
+            let excluded = blamed(failure);
+            let next = orchestrator
+                .next_params(dkg, &excluded)
+                .expect("valid params");
+            (excluded, next.context().clone())
+        };
+
+        // Two members time out waiting for different ones: they retry among all of them.
+        let (excluded, first) = next_context(0, &failure(FailureReason::Timeout, names[3]));
+        assert!(excluded.is_empty());
+        let (_, second) = next_context(1, &failure(FailureReason::Timeout, names[4]));
+        assert_eq!(first, second);
+        // Nor do the non-voters seen by a single member exclude them.
+        let non_voters =
+            FailureReason::KeyGen(Error::TooManyNonVoters(vec![3, 4].into_iter().collect()));
+        let (excluded, third) = next_context(2, &failure(non_voters, names[3]));
+        assert!(excluded.is_empty());
+        assert_eq!(first, third);
+        let dkg = &orchestrator(&peer_ids, names[0]).params.dkg;
+        assert_eq!(first.xornames, dkg.context().xornames);
+        assert_ne!(first.keygenid, dkg.context().keygenid);
+
+        // Whereas the members blamed by a quorum are excluded whatever each member saw.
+        let report = AbortReport {
+            reason: AbortReason::Timeout(Phase::Contribution),
+            blamed: std::iter::once(names[4]).collect(),
+            aborting: names[..4].iter().copied().collect(),
+        };
+        let aborted = FailureReason::Aborted(report);
+        let (excluded, first) = next_context(0, &failure(aborted.clone(), names[3]));
+        assert_eq!(excluded, std::iter::once(names[4]).collect());
+        let (_, second) = next_context(1, &failure(aborted, names[2]));
+        assert_eq!(first, second);
+        assert!(!first.xornames.contains(&names[4]));
+    }
+
+    #[test]
+    fn no_session_without_enough_members_or_with_ourselves_excluded() {
+        let peer_ids = create_ids(5);
+        let names: BTreeSet<XorName> = peer_ids.iter().map(|id| id.name()).collect();
+        let our_id = *names.iter().next().expect("five names");
+        let orchestrator = orchestrator(&peer_ids, our_id).min_members(4);
+        let dkg = &orchestrator.params.dkg;
+
+        let others: Vec<XorName> = names.iter().copied().skip(1).collect();
+        assert!(orchestrator
+            .next_params(dkg, &others[..1].iter().copied().collect())
+            .is_ok());
+        assert_eq!(
+            orchestrator.next_params(dkg, &others[..2].iter().copied().collect()),
+            Err(Error::CommitteeTooSmall { n: 3, min: 4 })
+        );
+        assert_eq!(
+            orchestrator.next_params(dkg, &std::iter::once(our_id).collect()),
+            Err(Error::EvictedSelf)
+        );
+    }
+}
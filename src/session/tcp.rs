@@ -0,0 +1,340 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// https://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+use super::transport::{DkgTransport, TransportError};
+use crate::key_gen::message::Message;
+use async_trait::async_trait;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tokio::task::{JoinHandle, JoinSet};
+use tokio::time;
+use xor_name::{XorName, XOR_NAME_LEN};
+
+/// The largest message accepted from a peer, in bytes.
+const MAX_FRAME_LEN: usize = 16 * 1024 * 1024;
+/// How many times a message is tried before it is given up on, e.g. because the peer has not
+/// started listening yet, and how long to wait in between.
+const SEND_ATTEMPTS: usize = 50;
+const RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// A `DkgTransport` over plain TCP, for running a session among processes.
+///
+/// Each member connects to every other member it sends to and first writes its 32 byte name,
+/// then each message as a 4 byte big endian length followed by the bincode serialised `Message`.
+/// The names are NOT authenticated, so this is only meant for trusted networks, e.g. localhost.
+///
+/// Messages are sent in order by a background task per peer, which reconnects as needed. A
+/// message that cannot be delivered within a few seconds is dropped, which the `KeyGen` tolerates
+/// as it would an unresponsive peer. Call `close` before the runtime shuts down for the messages
+/// still queued to go out.
+pub struct TcpTransport {
+    our_id: XorName,
+    local_addr: SocketAddr,
+    peers: BTreeMap<XorName, UnboundedSender<Arc<[u8]>>>,
+    inbox: UnboundedReceiver<(XorName, Message)>,
+    listener: JoinHandle<()>,
+    writers: Vec<JoinHandle<()>>,
+}
+
+impl TcpTransport {
+    /// Listens on `addr` and sends to the `peers` at their given addresses. `peers` may contain
+    /// ourselves, so that all members can share the same map.
+    pub async fn bind(
+        our_id: XorName,
+        addr: SocketAddr,
+        peers: BTreeMap<XorName, SocketAddr>,
+    ) -> io::Result<TcpTransport> {
+        Self::new(our_id, TcpListener::bind(addr).await?, peers)
+    }
+
+    /// Same as `bind`, with an already bound listener, e.g. to listen on a port picked by the
+    /// operating system. Must be called within a tokio runtime.
+    pub fn new(
+        our_id: XorName,
+        listener: TcpListener,
+        peers: BTreeMap<XorName, SocketAddr>,
+    ) -> io::Result<TcpTransport> {
+        let local_addr = listener.local_addr()?;
+        let peers: BTreeMap<_, _> = peers
+            .into_iter()
+            .filter(|(name, _)| *name != our_id)
+            .collect();
+
+        let (inbox_sender, inbox) = mpsc::unbounded_channel();
+        let names = peers.keys().copied().collect();
+        let listener = tokio::spawn(listen(listener, names, inbox_sender));
+        let mut writers = Vec::new();
+        let peers = peers
+            .into_iter()
+            .map(|(name, addr)| {
+                let (sender, queue) = mpsc::unbounded_channel();
+                // Not aborted on drop, so that our last messages still go out once we are done.
+                writers.push(tokio::spawn(write_to_peer(our_id, name, addr, queue)));
+                (name, sender)
+            })
+            .collect();
+
+        Ok(TcpTransport {
+            our_id,
+            local_addr,
+            peers,
+            inbox,
+            listener,
+            writers,
+        })
+    }
+
+    pub fn our_id(&self) -> XorName {
+        self.our_id
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Stops listening and waits for the queued messages to be sent or given up on.
+    pub async fn close(mut self) {
+        self.listener.abort();
+        self.peers.clear();
+        for writer in std::mem::take(&mut self.writers) {
+            let _ = writer.await;
+        }
+    }
+
+    fn queue(&self, peer: XorName, frame: Arc<[u8]>) -> Result<(), TransportError> {
+        self.peers
+            .get(&peer)
+            .ok_or(TransportError::UnknownPeer(peer))?
+            .send(frame)
+            .map_err(|_| TransportError::Closed)
+    }
+}
+
+impl Drop for TcpTransport {
+    fn drop(&mut self) {
+        self.listener.abort();
+    }
+}
+
+#[async_trait]
+impl DkgTransport for TcpTransport {
+    async fn send_to(&mut self, peer: XorName, msg: Message) -> Result<(), TransportError> {
+        self.queue(peer, frame(&msg)?)
+    }
+
+    async fn broadcast(&mut self, msg: Message) -> Result<(), TransportError> {
+        let frame = frame(&msg)?;
+        for peer in self.peers.keys() {
+            self.queue(*peer, frame.clone())?;
+        }
+        Ok(())
+    }
+
+    async fn receive(&mut self) -> Option<(XorName, Message)> {
+        self.inbox.recv().await
+    }
+}
+
+fn frame(msg: &Message) -> Result<Arc<[u8]>, TransportError> {
+    let bytes = bincode::serialize(msg)
+        .map_err(|error| TransportError::Serialisation(error.to_string()))?;
+    let mut frame = Vec::with_capacity(4 + bytes.len());
+    frame.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+    frame.extend_from_slice(&bytes);
+    Ok(frame.into())
+}
+
+// Accepts connections until aborted, which also aborts the connections.
+async fn listen(
+    listener: TcpListener,
+    names: BTreeSet<XorName>,
+    inbox: UnboundedSender<(XorName, Message)>,
+) {
+    let names = Arc::new(names);
+    let mut connections = JoinSet::new();
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, addr)) => {
+                    let names = names.clone();
+                    let inbox = inbox.clone();
+                    let _ = connections.spawn(async move {
+                        if let Err(error) = read_from_peer(stream, &names, inbox).await {
+                            debug!("Connection from {} closed: {}", addr, error);
+                        }
+                    });
+                }
+                Err(error) => warn!("Failed to accept a connection: {}", error),
+            },
+            Some(_) = connections.join_next() => {}
+        }
+    }
+}
+
+async fn read_from_peer(
+    mut stream: TcpStream,
+    names: &BTreeSet<XorName>,
+    inbox: UnboundedSender<(XorName, Message)>,
+) -> io::Result<()> {
+    let mut name = [0; XOR_NAME_LEN];
+    let _ = stream.read_exact(&mut name).await?;
+    let sender = XorName(name);
+    if !names.contains(&sender) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unknown peer {}", sender),
+        ));
+    }
+    loop {
+        let len = stream.read_u32().await? as usize;
+        if len > MAX_FRAME_LEN {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("frame of {} bytes from {}", len, sender),
+            ));
+        }
+        let mut bytes = vec![0; len];
+        let _ = stream.read_exact(&mut bytes).await?;
+        let msg = bincode::deserialize(&bytes)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+        if inbox.send((sender, msg)).is_err() {
+            return Ok(());
+        }
+    }
+}
+
+async fn write_to_peer(
+    our_id: XorName,
+    peer: XorName,
+    addr: SocketAddr,
+    mut queue: UnboundedReceiver<Arc<[u8]>>,
+) {
+    let mut stream = None;
+    // Once a message was given up on, the following ones are only tried once until the peer is
+    // back, so that closing does not wait for an unreachable peer again and again.
+    let mut reachable = true;
+    while let Some(frame) = queue.recv().await {
+        let attempts = if reachable { SEND_ATTEMPTS } else { 1 };
+        let mut sent = false;
+        for _ in 0..attempts {
+            if stream.is_none() {
+                stream = connect(our_id, addr).await.ok();
+            }
+            if let Some(connection) = stream.as_mut() {
+                if connection.write_all(&frame).await.is_ok() {
+                    sent = true;
+                    break;
+                }
+                stream = None;
+            }
+            time::sleep(RETRY_DELAY).await;
+        }
+        if !sent {
+            warn!("Dropped a message to {} at {}", peer, addr);
+        }
+        reachable = sent;
+    }
+}
+
+async fn connect(our_id: XorName, addr: SocketAddr) -> io::Result<TcpStream> {
+    let mut stream = TcpStream::connect(addr).await?;
+    stream.set_nodelay(true)?;
+    stream.write_all(&our_id.0).await?;
+    Ok(stream)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dev_utils::{create_ids, PeerId};
+    use crate::key_gen::{params::DkgParams, sharexorname::ShareXorName};
+    use crate::session::{run_dkg, SessionParams, TokioClock};
+
+    // Binds a transport for each of the members on localhost.
+    async fn localhost_network(peer_ids: &[PeerId]) -> BTreeMap<XorName, TcpTransport> {
+        let mut listeners = BTreeMap::new();
+        for peer_id in peer_ids {
+            let listener = TcpListener::bind("127.0.0.1:0").await.expect("bound");
+            let _ = listeners.insert(peer_id.name(), listener);
+        }
+        let addrs: BTreeMap<_, _> = listeners
+            .iter()
+            .map(|(name, listener)| (*name, listener.local_addr().expect("bound")))
+            .collect();
+        listeners
+            .into_iter()
+            .map(|(name, listener)| {
+                let transport = TcpTransport::new(name, listener, addrs.clone()).expect("created");
+                (name, transport)
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn delivers_messages_with_their_sender() {
+        let mut transports = localhost_network(&create_ids(3)).await;
+        let names: Vec<XorName> = transports.keys().copied().collect();
+        let msg = Message::Complaint {
+            key_gen_id: 7,
+            target: 1,
+            context: ShareXorName::from_xornames(names.clone()),
+            msg: b"test".to_vec(),
+        };
+
+        let stranger = XorName::random();
+        let sender = transports.get_mut(&names[0]).expect("transport");
+        assert_eq!(
+            sender.send_to(stranger, msg.clone()).await,
+            Err(TransportError::UnknownPeer(stranger))
+        );
+        sender.send_to(names[1], msg.clone()).await.expect("queued");
+        sender.broadcast(msg.clone()).await.expect("queued");
+
+        for name in &names[1..] {
+            let receiver = transports.get_mut(name).expect("transport");
+            assert_eq!(receiver.receive().await, Some((names[0], msg.clone())));
+        }
+        let receiver = transports.get_mut(&names[1]).expect("transport");
+        assert_eq!(receiver.receive().await, Some((names[0], msg)));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn all_members_complete_over_localhost() {
+        let peer_ids = create_ids(4);
+        let transports = localhost_network(&peer_ids).await;
+        let context = ShareXorName::from_xornames(transports.keys().copied().collect());
+        let handles: Vec<_> = peer_ids
+            .iter()
+            .zip(transports)
+            .map(|(peer_id, (our_id, transport))| {
+                let dkg = DkgParams::builder(our_id, context.clone(), 2)
+                    .identity(peer_id.identity(&peer_ids))
+                    .build()
+                    .expect("valid params");
+                let mut params = SessionParams::new(dkg);
+                params.phase_timeout = Duration::from_millis(500);
+                params.resend_interval = Duration::from_millis(200);
+                tokio::spawn(run_dkg(transport, params, TokioClock))
+            })
+            .collect();
+
+        let mut public_keys = BTreeSet::new();
+        for handle in handles {
+            let outcome = handle.await.expect("no panic").expect("completed");
+            let _ = public_keys.insert(outcome.public_key_set.public_key());
+        }
+        assert_eq!(public_keys.len(), 1);
+    }
+}
@@ -0,0 +1,125 @@
+// Copyright 2020 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under the MIT license <LICENSE-MIT
+// https://opensource.org/licenses/MIT> or the Modified BSD license <LICENSE-BSD
+// https://opensource.org/licenses/BSD-3-Clause>, at your option. This file may not be copied,
+// modified, or distributed except according to those terms. Please review the Licences for the
+// specific language governing permissions and limitations relating to use of the SAFE Network
+// Software.
+
+use crate::key_gen::message::Message;
+use async_trait::async_trait;
+use std::collections::{BTreeMap, BTreeSet};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use xor_name::XorName;
+
+/// An error of a `DkgTransport`.
+#[non_exhaustive]
+#[derive(Clone, Debug, Eq, PartialEq, thiserror::Error)]
+pub enum TransportError {
+    /// The transport can no longer send messages.
+    #[error("The transport is closed")]
+    Closed,
+    /// The peer is not a member of the session.
+    #[error("Unknown peer {0}")]
+    UnknownPeer(XorName),
+    /// The message could not be serialised.
+    #[error("Failed to serialise a message: {0}")]
+    Serialisation(String),
+}
+
+/// How the members of a DKG session, identified by their names, exchange messages.
+#[async_trait]
+pub trait DkgTransport: Send {
+    /// Sends `msg` to the member `peer`.
+    async fn send_to(&mut self, peer: XorName, msg: Message) -> Result<(), TransportError>;
+
+    /// Sends `msg` to all the other members.
+    async fn broadcast(&mut self, msg: Message) -> Result<(), TransportError>;
+
+    /// Waits for the next message from any other member and returns it with the name of its
+    /// sender, or returns `None` once no more messages can arrive. Must be cancel safe, as it is
+    /// raced against the phase timers.
+    async fn receive(&mut self) -> Option<(XorName, Message)>;
+}
+
+#[async_trait]
+impl<T: DkgTransport + ?Sized> DkgTransport for &mut T {
+    async fn send_to(&mut self, peer: XorName, msg: Message) -> Result<(), TransportError> {
+        (**self).send_to(peer, msg).await
+    }
+
+    async fn broadcast(&mut self, msg: Message) -> Result<(), TransportError> {
+        (**self).broadcast(msg).await
+    }
+
+    async fn receive(&mut self) -> Option<(XorName, Message)> {
+        (**self).receive().await
+    }
+}
+
+/// An in-memory `DkgTransport`, mostly for tests.
+pub struct ChannelTransport {
+    our_id: XorName,
+    peers: BTreeMap<XorName, UnboundedSender<(XorName, Message)>>,
+    inbox: UnboundedReceiver<(XorName, Message)>,
+}
+
+impl ChannelTransport {
+    /// Creates a transport for each of `names`, all connected to each other.
+    pub fn network(names: &BTreeSet<XorName>) -> BTreeMap<XorName, ChannelTransport> {
+        let (senders, inboxes): (BTreeMap<_, _>, Vec<_>) = names
+            .iter()
+            .map(|name| {
+                let (sender, inbox) = mpsc::unbounded_channel();
+                ((*name, sender), (*name, inbox))
+            })
+            .unzip();
+        inboxes
+            .into_iter()
+            .map(|(our_id, inbox)| {
+                let peers = senders
+                    .iter()
+                    .filter(|(name, _)| **name != our_id)
+                    .map(|(name, sender)| (*name, sender.clone()))
+                    .collect();
+                (
+                    our_id,
+                    ChannelTransport {
+                        our_id,
+                        peers,
+                        inbox,
+                    },
+                )
+            })
+            .collect()
+    }
+
+    pub fn our_id(&self) -> XorName {
+        self.our_id
+    }
+}
+
+#[async_trait]
+impl DkgTransport for ChannelTransport {
+    async fn send_to(&mut self, peer: XorName, msg: Message) -> Result<(), TransportError> {
+        let sender = self
+            .peers
+            .get(&peer)
+            .ok_or(TransportError::UnknownPeer(peer))?;
+        // Members that already completed the session dropped their end, which is not an error.
+        let _ = sender.send((self.our_id, msg));
+        Ok(())
+    }
+
+    async fn broadcast(&mut self, msg: Message) -> Result<(), TransportError> {
+        for sender in self.peers.values() {
+            let _ = sender.send((self.our_id, msg.clone()));
+        }
+        Ok(())
+    }
+
+    async fn receive(&mut self) -> Option<(XorName, Message)> {
+        self.inbox.recv().await
+    }
+}